@@ -1,128 +1,326 @@
-use image::GenericImageView;
-use monolith_solver::create_ahk::write_solving_steps;
-use monolith_solver::errors::MyError;
-use monolith_solver::map::{MonolithMap, SolvingMethods};
-use std::convert::TryInto;
-use std::io::Read;
-use std::path::PathBuf;
+use monolith_solver::calibration::{ColorModel, DEFAULT_CALIBRATION_FILE};
+use monolith_solver::cluster::TileClassifier;
+use monolith_solver::create_ahk::{write_mark_tiles_script, write_solving_steps};
+use monolith_solver::detect::detect_grid;
+use monolith_solver::difficulty::Difficulty;
+use monolith_solver::errors::{MyError, WithPath};
+use monolith_solver::geometry::{GeometrySource, GridGeometry};
+use monolith_solver::input::{find_images, get_inputs, ImageInput, STDIN_ARGUMENT};
+use monolith_solver::map::{
+    get_hints, replay_game, HeuristicWeights, MapFormat, MonolithMap, Objective, Objectives,
+    ScoreRules, SolverOptions, SolvingMethods, TargetSpec, MAX_TILE, MAX_X, MAX_Y,
+};
+use monolith_solver::map_file::{hash_source, MapFile, MapMetadata};
+use monolith_solver::overlay::draw_recognition;
+use monolith_solver::recognize::{RecognitionOptions, RecognitionResult, Recognizer};
+use monolith_solver::render::{render_map, RenderOptions};
+use monolith_solver::tune::{load_maps, tune, TuningStrategy};
+use monolith_solver::video::{find_new_board, read_frame_files, RawFrames};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use structopt::StructOpt;
 
+/// Target of `Method9` without `--target`, as read by older versions.
+const TARGET_FILE: &str = "tiles.txt";
+
 #[derive(StructOpt, Debug)]
 struct Args {
+    /// Screenshots to solve: files, patterns like `screens/*.jpg` or `-` for stdin
+    /// (all PNG, JPEG, BMP and TGA images in the current directory by default)
+    images: Vec<String>,
+
     #[structopt(short, long, default_value = "Method4")]
     method: SolvingMethods,
 
     #[structopt(long)]
     generate_map: bool,
-}
 
-fn get_image_files() -> Vec<PathBuf> {
-    let cwd = std::env::current_dir().expect("Failed to obtain CWD.");
-    let contents = cwd.read_dir().expect("Failed to read from CWD.");
-    let mut results = vec![];
-    for entry in contents {
-        let path = entry.expect("Failed to get DirEntry information").path();
-        if let Some(extension) = path.extension() {
-            if extension
-                .to_str()
-                .expect("Failed to get DirEntry extension.")
-                .to_lowercase()
-                == "png"
-            {
-                results.push(path);
-            }
-        }
-    }
-    results
+    /// Format of written `.map` files: `json` or `text` (a line of characters per
+    /// row, easier to fix by hand). Both are read
+    #[structopt(long, default_value = "json")]
+    map_format: MapFormat,
+
+    /// Writes the recognised board with the confidence of every tile to a
+    /// `.recognition.json` file next to the screenshot
+    #[structopt(long)]
+    report: bool,
+
+    /// Writes a copy of the screenshot with the recognised tiles outlined and
+    /// labelled (type at the top, confidence in percent at the bottom) to a
+    /// `.debug.png` file next to it, ambiguous tiles are outlined in red
+    #[structopt(long)]
+    debug_image: bool,
+
+    /// Tiles recognised with a lower confidence (from 0 to 1) stop solving until
+    /// they are fixed in the `.map` file
+    #[structopt(long, default_value = "0.2")]
+    min_confidence: f64,
+
+    /// Grid position on screenshots: `auto` (scaled from the screenshot size),
+    /// `detect` (searched for on the screenshot), a built-in
+    /// profile (`1280x720`, `1600x900`, `1920x1080`, `2560x1440`, `3840x2160`)
+    /// or a JSON profile file
+    #[structopt(long, default_value = "auto")]
+    geometry: String,
+
+    /// Overrides the left edge of the grid from the geometry profile
+    #[structopt(long)]
+    origin_x: Option<f64>,
+
+    /// Overrides the top edge of the grid from the geometry profile
+    #[structopt(long)]
+    origin_y: Option<f64>,
+
+    /// Overrides the tile size from the geometry profile
+    #[structopt(long)]
+    tile_size: Option<f64>,
+
+    /// Overrides the tile border from the geometry profile
+    #[structopt(long)]
+    border_width: Option<f64>,
+
+    /// JSON file with heuristic weights (see `tune` command)
+    #[structopt(long, parse(from_os_str))]
+    weights: Option<PathBuf>,
+
+    /// Comma-separated objectives to compare solutions by, most important first
+    /// (`tiles`, `clicks`, `travel`, `score`, `target`)
+    #[structopt(long, default_value = "tiles")]
+    objectives: Objectives,
+
+    /// JSON file with in-game scoring rules used by the `score` objective
+    #[structopt(long, parse(from_os_str))]
+    score_rules: Option<PathBuf>,
+
+    /// JSON file with the board pattern for the `target` objective and Method9
+    /// (defaults to `tiles.txt` for Method9)
+    #[structopt(long, parse(from_os_str))]
+    target: Option<PathBuf>,
+
+    /// Seconds after which the solving method stops searching and takes the best solution found
+    #[structopt(long, default_value = "60")]
+    time_limit: f64,
+
+    /// JSON file with calibrated tile colors (see `calibrate` command,
+    /// defaults to `calibration.json` if there is one)
+    #[structopt(long, parse(from_os_str))]
+    calibration: Option<PathBuf>,
+
+    /// How tile colors are recognised: `colors` (matched against the calibrated
+    /// colors) or `clusters` (grouped by similarity first, for dark or bright screenshots)
+    #[structopt(long, default_value = "colors")]
+    classifier: TileClassifier,
+
+    /// Difficulty of the boards (`gentle`, `kind` or `mean`), screenshots with a
+    /// different number of tile colors are refused. `auto` accepts any board, but
+    /// only Mean rules are solved
+    #[structopt(long, default_value = "mean")]
+    difficulty: String,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
 }
 
-fn get_average_tile_color(image: &image::DynamicImage, tile_x: u32, tile_y: u32) -> (u8, u8, u8) {
-    let grid_start_x = 80;
-    let grid_start_y = 80;
-    let border_width = 4;
-    let tile_size = 80;
+#[derive(StructOpt, Debug)]
+enum Command {
+    /// Finds heuristic weights which leave the fewest tiles on a set of boards
+    Tune {
+        /// Directory with `.map` files
+        #[structopt(parse(from_os_str))]
+        directory: PathBuf,
 
-    let current_tile_start_x = grid_start_x + tile_x * tile_size;
-    let current_tile_start_y = grid_start_y + tile_y * tile_size;
+        #[structopt(short, long, default_value = "Method10")]
+        method: SolvingMethods,
 
-    let tile = image.view(
-        current_tile_start_x + border_width,
-        current_tile_start_y + border_width,
-        tile_size - border_width * 2,
-        tile_size - border_width * 2,
-    );
+        /// `random` or `evolution`
+        #[structopt(short, long, default_value = "random")]
+        strategy: TuningStrategy,
 
-    let mut r: u32 = 0;
-    let mut g: u32 = 0;
-    let mut b: u32 = 0;
-    let mut count: u32 = 0;
-    for pixel in tile.pixels() {
-        count += 1;
-
-        let pr = u32::from(pixel.2[0]);
-        let pg = u32::from(pixel.2[1]);
-        let pb = u32::from(pixel.2[2]);
-
-        r += pr * pr;
-        g += pg * pg;
-        b += pb * pb;
-    }
-
-    (
-        f64::from(r / count).sqrt() as u8,
-        f64::from(g / count).sqrt() as u8,
-        f64::from(b / count).sqrt() as u8,
-    )
-}
-
-fn get_tile_group(color: (u8, u8, u8)) -> u8 {
-    let groups: [(u8, (u8, u8, u8)); 4] = [
-        (1, (189, 187, 187)),
-        (2, (236, 145, 187)),
-        (3, (211, 171, 110)),
-        (4, (99, 166, 184)),
-    ];
-
-    let cmp_lambda = |cc1, cc2| {
-        let c1 = u32::from(cc1);
-        let c2 = u32::from(cc2);
-        let delta_percentage = 15;
-        c1 > (c2 - (c2 * delta_percentage) / 100) && c1 < (c2 + (c2 * delta_percentage) / 100)
-    };
+        /// Number of evaluated weight vectors
+        #[structopt(short, long, default_value = "100")]
+        iterations: usize,
 
-    for (group_type, group_color) in &groups {
-        let c1 = cmp_lambda(color.0, group_color.0);
-        let c2 = cmp_lambda(color.1, group_color.1);
-        let c3 = cmp_lambda(color.2, group_color.2);
+        #[structopt(short, long, default_value = "weights.json", parse(from_os_str))]
+        output: PathBuf,
+    },
+    /// Finds the tile grid on a screenshot and prints its geometry
+    DetectGrid {
+        #[structopt(parse(from_os_str))]
+        image: PathBuf,
 
-        if c1 && c2 && c3 {
-            return *group_type;
-        }
+        /// Saves the geometry as a profile for `--geometry`
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Suggests the best next clicks for a board, solving it with `--method`
+    Hint {
+        /// Screenshot or `.map` file of the current board
+        #[structopt(parse(from_os_str))]
+        board: PathBuf,
+
+        /// Number of suggested clicks
+        #[structopt(short, long, default_value = "3")]
+        count: usize,
+
+        /// Seconds to spend on all candidates together
+        #[structopt(short, long, default_value = "5")]
+        time: f64,
+    },
+    /// Computes tile colors from a screenshot with a known board
+    Calibrate {
+        #[structopt(parse(from_os_str))]
+        image: PathBuf,
+
+        /// `.map` file with the correct board (defaults to the one next to the
+        /// screenshot, if there is none the board is labeled on the command line)
+        #[structopt(long, parse(from_os_str))]
+        map: Option<PathBuf>,
+
+        #[structopt(short, long, default_value = "calibration.json", parse(from_os_str))]
+        output: PathBuf,
+    },
+    /// Draws a `.map` file as a screenshot, with `--geometry` and the calibrated colors
+    Render {
+        #[structopt(parse(from_os_str))]
+        map: PathBuf,
+
+        #[structopt(short, long, default_value = "render.png", parse(from_os_str))]
+        output: PathBuf,
+
+        #[structopt(long, default_value = "1920")]
+        width: u32,
+
+        #[structopt(long, default_value = "1080")]
+        height: u32,
+
+        /// Largest random change of every color channel
+        #[structopt(long, default_value = "0")]
+        noise: f64,
+
+        /// Factor for all colors
+        #[structopt(long, default_value = "1")]
+        brightness: f64,
+
+        /// Compresses the image as JPEG with this quality (1-100) first
+        #[structopt(long)]
+        jpeg_quality: Option<u8>,
+
+        /// Draws a mouse cursor pointing at this pixel (`x,y`)
+        #[structopt(long, parse(try_from_str = parse_point))]
+        cursor: Option<(u32, u32)>,
+
+        #[structopt(long, default_value = "0")]
+        seed: u64,
+    },
+    /// Finds the first frame of a recording with a new, fully populated board and
+    /// writes the board to a `.map` file
+    Video {
+        /// Directory with the frames as images, or `-` for raw RGB frames on stdin
+        /// (`ffmpeg -i run.mp4 -f rawvideo -pix_fmt rgb24 -`)
+        input: String,
+
+        /// Width of raw frames
+        #[structopt(long, default_value = "1920")]
+        width: u32,
+
+        /// Height of raw frames
+        #[structopt(long, default_value = "1080")]
+        height: u32,
+
+        /// Number of frames in a row which have to show the same board
+        #[structopt(long, default_value = "2")]
+        stable_frames: usize,
+
+        #[structopt(short, long, default_value = "video.map", parse(from_os_str))]
+        output: PathBuf,
+    },
+    /// Finds the clicks played between boards of a game and compares them with the
+    /// solver (`--method`)
+    Replay {
+        /// Screenshots or `.map` files of the game in order, or patterns like
+        /// `game/*.png`
+        boards: Vec<String>,
+
+        /// Number of clicks which may be missing between two boards
+        #[structopt(long, default_value = "3")]
+        max_clicks: usize,
+
+        /// Writes the played clicks and the comparison to a JSON file
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Writes an AutoHotkey script for the grid of `--geometry`, which saves the tiles
+    /// under the cursor (Space) to `tiles.txt` when it's closed (Q)
+    MarkTiles {
+        /// Screenshot of the screen the script is for, a 1920x1080 screen without one
+        #[structopt(parse(from_os_str))]
+        image: Option<PathBuf>,
+
+        #[structopt(short, long, default_value = "mark_tiles.ahk", parse(from_os_str))]
+        output: PathBuf,
+    },
+}
+
+fn parse_point(value: &str) -> Result<(u32, u32), String> {
+    let mut parts = value.split(',').map(|x| x.trim().parse::<u32>());
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(x)), Some(Ok(y)), None) => Ok((x, y)),
+        _ => Err(format!("Expected a point like '100,200', got '{}'", value)),
     }
-    0
 }
 
-fn generate_monolith_map(image_data: &[u8]) -> Result<MonolithMap, MyError> {
-    let image = image::load_from_memory(image_data)?;
-    let mut map = MonolithMap::default();
+/// Time from seconds on the command line, which `Duration` can't hold when negative,
+/// not a number or too large.
+fn get_duration(seconds: f64) -> Result<Duration, MyError> {
+    if seconds.is_finite() && seconds >= 0.0 && seconds < u64::MAX as f64 {
+        Ok(Duration::from_secs_f64(seconds))
+    } else {
+        Err(MyError::InvalidInput(format!(
+            "'{}' is not a valid number of seconds",
+            seconds
+        )))
+    }
+}
 
-    for x in 0..22usize {
-        for y in 0..11usize {
-            let avg_color =
-                get_average_tile_color(&image, x.try_into().unwrap(), y.try_into().unwrap());
-            let tile_group = get_tile_group(avg_color);
-            map.set(x, y, tile_group);
-        }
+fn generate_monolith_map(
+    image_data: &[u8],
+    geometry: &GeometrySettings,
+    colors: &ColorSettings,
+    debug_image: Option<&Path>,
+) -> Result<RecognitionResult, MyError> {
+    let image = image::load_from_memory(image_data)?;
+    let recognition = colors.get_recognizer(geometry, &image)?.recognize(&image)?;
+    if recognition.difficulty != Difficulty::Mean {
+        println!(
+            "The board looks like {:?} difficulty, it is solved with Mean rules anyway.",
+            recognition.difficulty
+        );
     }
-    Ok(map)
+    if let Some(path) = debug_image {
+        println!("Writing debug image to {:?}.", path);
+        draw_recognition(
+            &image,
+            &recognition.geometry,
+            &recognition.get_tiles(),
+            colors.options.min_confidence,
+        )
+        .save(path)
+        .with_path(path)?;
+    }
+    Ok(recognition)
 }
 
-fn get_monolith_map(image: &PathBuf, generate_map: bool) -> Result<MonolithMap, MyError> {
-    let data_filepath = {
-        let mut temp = image.clone();
-        temp.set_extension("map");
-        temp
-    };
+fn get_monolith_map(
+    image: &ImageInput,
+    generate_map: bool,
+    report: bool,
+    debug_image: bool,
+    map_format: MapFormat,
+    geometry: &GeometrySettings,
+    colors: &ColorSettings,
+) -> Result<(MonolithMap, Option<GridGeometry>), MyError> {
+    let data_filepath = image.path().with_extension("map");
     if data_filepath.exists() {
         println!(
             "Using existing map data from {:?}.",
@@ -130,55 +328,811 @@ fn get_monolith_map(image: &PathBuf, generate_map: bool) -> Result<MonolithMap,
                 .file_name()
                 .unwrap_or_else(|| std::ffi::OsStr::new("???"))
         );
-        let mut data_file = std::fs::File::open(&data_filepath)?;
-        let mut buffer = String::new();
-        data_file.read_to_string(&mut buffer)?;
-        Ok(serde_json::from_str::<MonolithMap>(&buffer)?)
+        if debug_image && image.is_map() {
+            println!("There is no screenshot to write a debug image of.");
+        } else if debug_image {
+            println!(
+                "No debug image is written, remove {:?} to recognise the screenshot again.",
+                data_filepath
+            );
+        }
+        Ok((MonolithMap::load(&data_filepath)?, None))
     } else {
-        let image_data = {
-            let mut temp = std::fs::File::open(&image)?;
-            let mut buffer = Vec::new();
-            temp.read_to_end(&mut buffer)?;
-            buffer
-        };
+        let image_data = image.read()?;
 
-        let map_data = generate_monolith_map(&image_data)?;
+        let debug_image_filepath = image.path().with_extension("debug.png");
+        let recognition = generate_monolith_map(
+            &image_data,
+            geometry,
+            colors,
+            Some(debug_image_filepath.as_path()).filter(|_| debug_image),
+        )?;
+        let map_data = recognition.map.clone();
+        let mut metadata = MapMetadata::from_recognition(&recognition);
+        metadata.source = image
+            .path()
+            .file_name()
+            .map(|x| x.to_string_lossy().into_owned());
+        metadata.source_hash = Some(hash_source(&image_data));
+        metadata.calibration = colors.calibration.clone();
+        let mut map_file = MapFile::new(map_data.clone(), metadata);
+        if report {
+            let report_filepath = image.path().with_extension("recognition.json");
+            println!("Writing recognition report to {:?}.", report_filepath);
+            let report_file =
+                std::fs::File::create(&report_filepath).with_path(&report_filepath)?;
+            serde_json::to_writer_pretty(report_file, &recognition).with_path(&report_filepath)?;
+        }
+        if !recognition.occluded.is_empty() {
+            println!(
+                "Tiles {:?} are partially covered, recognised from the visible part.",
+                recognition.occluded
+            );
+        }
+        if !recognition.ambiguous.is_empty() {
+            println!("Some tiles can't be recognised reliably:");
+            for tile in &recognition.ambiguous {
+                println!(
+                    "  ({}, {}) looks like type {}, {:.0}% confidence",
+                    tile.0,
+                    tile.1,
+                    map_data.get(tile.0, tile.1),
+                    recognition.confidence[tile.1][tile.0] * 100.0
+                );
+            }
+            // the map file takes precedence over the screenshot, so it can be corrected by hand
+            map_file.mark_unverified();
+            map_file.save(&data_filepath, map_format)?;
+            println!(
+                "Fix them in {:?} and run again, or lower `--min-confidence`.",
+                data_filepath
+            );
+            return Err(MyError::AmbiguousTiles(recognition.ambiguous));
+        }
         if generate_map {
-            let data_file = std::fs::File::create(&data_filepath)?;
             println!(
                 "Writing map data to {:?}.",
                 data_filepath
                     .file_name()
                     .unwrap_or_else(|| std::ffi::OsStr::new("???"))
             );
-            serde_json::to_writer(data_file, &map_data)?;
+            map_file.save(&data_filepath, map_format)?;
         }
 
-        Ok(map_data)
+        Ok((map_data, Some(recognition.geometry)))
     }
 }
 
+fn run_tune(
+    directory: PathBuf,
+    method: SolvingMethods,
+    strategy: TuningStrategy,
+    iterations: usize,
+    output: PathBuf,
+    options: &SolverOptions,
+) -> Result<(), MyError> {
+    if !method.uses_weights() {
+        println!("Solving method {:?} doesn't use heuristic weights.", method);
+        return Ok(());
+    }
+
+    let maps = load_maps(&directory)?;
+    if maps.is_empty() {
+        println!("No map files found in {:?}.", directory);
+        return Ok(());
+    }
+
+    println!(
+        "Tuning weights on {} maps (this may take a while).",
+        maps.len()
+    );
+    let (weights, score) = tune(&maps, &method, options, &strategy, iterations)?;
+    println!(
+        "Best weights leave {:.2} tiles on average: {:?}",
+        score, weights
+    );
+    println!("Writing weights to {:?}.", output);
+    weights.save(&output)
+}
+
+fn run_mark_tiles(
+    image: &Option<PathBuf>,
+    output: &Path,
+    geometry: &GeometrySettings,
+) -> Result<(), MyError> {
+    let geometry = match image {
+        Some(image) => geometry.resolve_for_input(&ImageInput::File(image.clone()))?,
+        None => geometry.resolve_for_size(1920, 1080)?,
+    };
+    println!("Writing the script to mark tiles to {:?}.", output);
+    write_mark_tiles_script(output, &geometry, TARGET_FILE)
+}
+
+fn run_detect_grid(image: &Path, output: &Option<PathBuf>) -> Result<(), MyError> {
+    let geometry = detect_grid(&image::open(image)?)?;
+    println!(
+        "Found a {}x{} grid at ({:.1}, {:.1}) with {:.2} px tiles.",
+        geometry.columns, geometry.rows, geometry.origin_x, geometry.origin_y, geometry.tile_size
+    );
+    if let Some(output) = output {
+        println!("Writing geometry profile to {:?}.", output);
+        geometry.save(output)?;
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_hint(
+    board: &Path,
+    count: usize,
+    time: f64,
+    method: &SolvingMethods,
+    options: &SolverOptions,
+    map_format: MapFormat,
+    geometry: &GeometrySettings,
+    colors: &ColorSettings,
+) -> Result<(), MyError> {
+    let time = get_duration(time)?;
+    let board = ImageInput::File(board.to_path_buf());
+    let (map, _) = get_monolith_map(&board, false, false, false, map_format, geometry, colors)?;
+    println!("Looking for the best next clicks (this may take a while).");
+    let hints = get_hints(&map, method, options, count, time)?;
+    if hints.is_empty() {
+        println!("There are no groups left to click.");
+    }
+    for (index, hint) in hints.iter().enumerate() {
+        println!(
+            "{}. Click ({}, {}) to remove {} tiles: {} tiles left in the end, {:.0}% confidence.",
+            index + 1,
+            hint.tile.0,
+            hint.tile.1,
+            hint.group_size,
+            hint.remaining_tiles,
+            hint.confidence * 100.0
+        );
+    }
+    Ok(())
+}
+
+/// Asks the user to correct the board recognised with the current colors, row by row.
+fn label_map(guess: &MonolithMap) -> Result<MonolithMap, MyError> {
+    println!(
+        "Enter every row as {} digits (0 for no tile, 1-4 for tile types), or an empty line to keep the guess.",
+        MAX_X
+    );
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut map = guess.clone();
+    let mut y = 0;
+    while y < MAX_Y {
+        let row = (0..MAX_X)
+            .map(|x| guess.get(x, y).to_string())
+            .collect::<String>();
+        print!("Row {:2} [{}]: ", y + 1, row);
+        std::io::stdout().flush()?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => return Err(MyError::Calibration("board labeling aborted".to_string())),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            y += 1;
+            continue;
+        }
+        let values = line
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| c.to_digit(10).filter(|value| *value <= u32::from(MAX_TILE)))
+            .collect::<Option<Vec<_>>>();
+        match values {
+            Some(values) if values.len() == MAX_X => {
+                for (x, value) in values.into_iter().enumerate() {
+                    map.try_set(x, y, value as u8)?;
+                }
+                y += 1;
+            }
+            _ => println!("Expected {} digits from 0 to 4.", MAX_X),
+        }
+    }
+    Ok(map)
+}
+
+fn run_calibrate(
+    image_path: &Path,
+    map_path: &Option<PathBuf>,
+    output: &Path,
+    geometry: &GeometrySettings,
+    colors: &ColorSettings,
+) -> Result<(), MyError> {
+    let image = image::open(image_path)?;
+    let recognizer = colors.get_recognizer(geometry, &image)?;
+    let (grid, tiles) = recognizer.sample_tiles(&image)?;
+
+    let default_map_path = image_path.with_extension("map");
+    let map = match map_path {
+        Some(path) => MonolithMap::load(path)?,
+        None if default_map_path.exists() => {
+            println!("Using board from {:?}.", default_map_path);
+            MonolithMap::load(&default_map_path)?
+        }
+        None => label_map(&recognizer.recognize_samples(grid, &tiles)?.map)?,
+    };
+
+    let samples = tiles
+        .iter()
+        .map(|(tile, sample)| (sample.color, map.get(tile.0, tile.1)))
+        .collect::<Vec<_>>();
+    let calibrated = ColorModel::calibrate(&samples)?;
+    for color in &calibrated.colors {
+        println!(
+            "Tile type {}: color ({:.0}, {:.0}, {:.0}) ± ({:.0}, {:.0}, {:.0}).",
+            color.tile_type,
+            color.centroid[0],
+            color.centroid[1],
+            color.centroid[2],
+            color.tolerance[0],
+            color.tolerance[1],
+            color.tolerance[2]
+        );
+    }
+    let mismatches = samples
+        .iter()
+        .filter(|(color, tile_type)| calibrated.classify(*color) != *tile_type)
+        .count();
+    if mismatches > 0 {
+        println!(
+            "Warning: {} tiles are still recognised wrong with the calibrated colors.",
+            mismatches
+        );
+    }
+    println!("Writing calibration to {:?}.", output);
+    calibrated.save(output)
+}
+
+fn run_render(
+    map: &Path,
+    output: &Path,
+    options: &RenderOptions,
+    geometry: &GeometrySettings,
+    colors: &ColorSettings,
+) -> Result<(), MyError> {
+    let map = MonolithMap::load(map)?;
+    // there is no screenshot to detect the grid on
+    let geometry = geometry.resolve_for_size(options.width, options.height)?;
+    geometry.validate(options.width, options.height)?;
+    let image = render_map(&map, &geometry, &colors.model, options)?;
+    println!("Writing rendered board to {:?}.", output);
+    image.save(output).with_path(output)
+}
+
+type Frames = Box<dyn Iterator<Item = Result<image::DynamicImage, MyError>>>;
+
+fn run_video(
+    input: &str,
+    size: (u32, u32),
+    stable_frames: usize,
+    output: &Path,
+    map_format: MapFormat,
+    geometry: &GeometrySettings,
+    colors: &ColorSettings,
+) -> Result<(), MyError> {
+    let no_board = || MyError::InvalidInput(format!("no frame of '{}' shows a full board", input));
+    let (frames, size): (Frames, _) = if input == STDIN_ARGUMENT {
+        (
+            Box::new(RawFrames::new(std::io::stdin(), size.0, size.1)),
+            size,
+        )
+    } else {
+        let files = find_images(Path::new(input))?;
+        // the frames of a recording all have the size of the first one
+        let size = match files.first() {
+            Some(file) => image::image_dimensions(file).with_path(file)?,
+            None => return Err(no_board()),
+        };
+        (Box::new(read_frame_files(files)), size)
+    };
+
+    let recognizer = colors.get_frame_recognizer(geometry, size)?;
+    let board = find_new_board(frames, &recognizer, stable_frames)?.ok_or_else(no_board)?;
+    println!(
+        "Found a new board in frame {}, writing map data to {:?}.",
+        board.index, output
+    );
+    let mut metadata = MapMetadata::from_recognition(&board.recognition);
+    metadata.source = Some(format!("{}, frame {}", input, board.index));
+    metadata.calibration = colors.calibration.clone();
+    MapFile::new(board.recognition.map, metadata).save(output, map_format)
+}
+
+/// Board of a screenshot for a replay, without writing any files: the `.map` file if
+/// there is one, otherwise the recognised board, which fails on ambiguous tiles.
+fn read_board(
+    input: &ImageInput,
+    geometry: &GeometrySettings,
+    colors: &ColorSettings,
+) -> Result<MonolithMap, MyError> {
+    let data_filepath = input.path().with_extension("map");
+    if data_filepath.exists() {
+        return MonolithMap::load(&data_filepath);
+    }
+    let recognition = generate_monolith_map(&input.read()?, geometry, colors, None)?;
+    if !recognition.ambiguous.is_empty() {
+        return Err(MyError::AmbiguousTiles(recognition.ambiguous));
+    }
+    Ok(recognition.map)
+}
+
+fn run_replay(
+    boards: &[String],
+    max_clicks: usize,
+    output: &Option<PathBuf>,
+    method: &SolvingMethods,
+    options: &SolverOptions,
+    geometry: &GeometrySettings,
+    colors: &ColorSettings,
+) -> Result<(), MyError> {
+    let inputs = get_inputs(boards)?;
+    if inputs.len() < 2 {
+        return Err(MyError::InvalidInput(
+            "a replay needs at least two boards".to_string(),
+        ));
+    }
+    // positions of the boards which can be read among the inputs
+    let mut positions = Vec::with_capacity(inputs.len());
+    let mut unreadable = vec![];
+    let mut maps = Vec::with_capacity(inputs.len());
+    for (index, input) in inputs.iter().enumerate() {
+        match read_board(input, geometry, colors) {
+            Ok(map) => {
+                positions.push(index);
+                maps.push(map);
+            }
+            Err(error) => {
+                println!(
+                    "Unable to read board {:?}, it is left out. Error: {}",
+                    input.path(),
+                    error.report()
+                );
+                unreadable.push(index);
+            }
+        }
+    }
+    if maps.len() < 2 {
+        return Err(MyError::InvalidInput(
+            "a replay needs at least two boards which can be read".to_string(),
+        ));
+    }
+
+    println!("Comparing the game with the solver (this may take a while).");
+    let mut replay = replay_game(&maps, method, options, max_clicks)?;
+    for click in &mut replay.clicks {
+        click.board = positions[click.board];
+    }
+    for index in &mut replay.skipped {
+        *index = positions[*index];
+    }
+    if !replay.skipped.is_empty() {
+        println!("These boards don't follow from the previous ones and are left out:");
+        for index in &replay.skipped {
+            println!("  {:?}", inputs[*index].path());
+        }
+    }
+    replay.skipped.extend(unreadable);
+    replay.skipped.sort_unstable();
+    for (index, click) in replay.clicks.iter().enumerate() {
+        print!(
+            "{}. Clicked ({}, {}) to remove {} tiles",
+            index + 1,
+            click.tile.0,
+            click.tile.1,
+            click.group_size
+        );
+        if click.lost_tiles() > 0 {
+            print!(
+                ", lost {} tiles: {} instead of {} tiles left in the end",
+                click.lost_tiles(),
+                click.best_after,
+                click.best_before
+            );
+        }
+        println!(".");
+    }
+    println!(
+        "Played {} clicks with {} tiles left, {:?} solves the first board with {} clicks and {} tiles left.",
+        replay.clicks.len(),
+        replay.remaining_tiles,
+        method,
+        replay.solver_path.len(),
+        replay.solver_remaining_tiles
+    );
+    if let Some(output) = output {
+        println!("Writing replay to {:?}.", output);
+        let file = std::fs::File::create(output).with_path(output)?;
+        serde_json::to_writer_pretty(file, &replay).with_path(output)?;
+    }
+    Ok(())
+}
+
+/// Geometry profile with the overrides from the command line.
+struct GeometrySettings {
+    source: GeometrySource,
+    origin_x: Option<f64>,
+    origin_y: Option<f64>,
+    tile_size: Option<f64>,
+    border_width: Option<f64>,
+}
+
+impl GeometrySettings {
+    fn from_args(args: &Args) -> Result<GeometrySettings, MyError> {
+        Ok(GeometrySettings {
+            source: GeometrySource::parse(&args.geometry)?,
+            origin_x: args.origin_x,
+            origin_y: args.origin_y,
+            tile_size: args.tile_size,
+            border_width: args.border_width,
+        })
+    }
+
+    fn has_overrides(&self) -> bool {
+        self.origin_x.is_some()
+            || self.origin_y.is_some()
+            || self.tile_size.is_some()
+            || self.border_width.is_some()
+    }
+
+    fn with_overrides(&self, geometry: GridGeometry) -> GridGeometry {
+        GridGeometry {
+            origin_x: self.origin_x.unwrap_or(geometry.origin_x),
+            origin_y: self.origin_y.unwrap_or(geometry.origin_y),
+            tile_size: self.tile_size.unwrap_or(geometry.tile_size),
+            border_width: self.border_width.unwrap_or(geometry.border_width),
+            ..geometry
+        }
+    }
+
+    fn resolve(&self, image: &image::DynamicImage) -> Result<GridGeometry, MyError> {
+        Ok(self.with_overrides(self.source.resolve(image)?))
+    }
+
+    /// Geometry for a screenshot of this size, fails for `detect`.
+    fn resolve_for_size(&self, width: u32, height: u32) -> Result<GridGeometry, MyError> {
+        Ok(self.with_overrides(self.source.resolve_for_size(width, height)?))
+    }
+
+    fn resolve_for_input(&self, image: &ImageInput) -> Result<GridGeometry, MyError> {
+        if image.is_map() {
+            // no screenshot to measure, `--geometry` tells the screen size
+            if self.source == GeometrySource::Auto {
+                println!(
+                    "The clicks are for a 1920x1080 screen, pass `--geometry` for other sizes."
+                );
+            }
+            return self.resolve_for_size(1920, 1080);
+        }
+        match self.source {
+            GeometrySource::Detect => self.resolve(&image.decode()?),
+            _ => {
+                // only the size matters, no need to decode the whole image
+                let (width, height) = image.dimensions()?;
+                self.resolve_for_size(width, height)
+            }
+        }
+    }
+}
+
+/// Tile colors and the way they are matched, from the command line.
+struct ColorSettings {
+    model: ColorModel,
+    /// Calibration file of the model, `None` for the built-in colors.
+    calibration: Option<String>,
+    options: RecognitionOptions,
+}
+
+impl ColorSettings {
+    fn from_args(args: &Args) -> Result<ColorSettings, MyError> {
+        let path = args
+            .calibration
+            .clone()
+            .or_else(|| Some(PathBuf::from(DEFAULT_CALIBRATION_FILE)).filter(|x| x.exists()));
+        let model = match &path {
+            Some(path) => ColorModel::load(path)?,
+            None => ColorModel::default(),
+        };
+        Ok(ColorSettings {
+            model,
+            calibration: path.map(|x| x.display().to_string()),
+            options: RecognitionOptions {
+                classifier: args.classifier.clone(),
+                min_confidence: args.min_confidence,
+                difficulty: match args.difficulty.as_str() {
+                    "auto" => None,
+                    value => Some(value.parse().map_err(MyError::InvalidInput)?),
+                },
+            },
+        })
+    }
+
+    /// Recognizer for the screenshot, with the grid geometry resolved for it.
+    fn get_recognizer(
+        &self,
+        geometry: &GeometrySettings,
+        image: &image::DynamicImage,
+    ) -> Result<Recognizer, MyError> {
+        Ok(Recognizer::new(
+            GeometrySource::Fixed(geometry.resolve(image)?),
+            self.model.clone(),
+            self.options.clone(),
+        ))
+    }
+
+    /// Recognizer for all frames of a recording, which have the same size. `detect`
+    /// finds the grid on every frame, so it can't be combined with the overrides.
+    fn get_frame_recognizer(
+        &self,
+        geometry: &GeometrySettings,
+        (width, height): (u32, u32),
+    ) -> Result<Recognizer, MyError> {
+        let source = match geometry.source {
+            GeometrySource::Detect if geometry.has_overrides() => {
+                return Err(MyError::InvalidGeometry(
+                    "the grid of a recording can't be detected and changed at once".to_string(),
+                ))
+            }
+            GeometrySource::Detect => GeometrySource::Detect,
+            _ => GeometrySource::Fixed(geometry.resolve_for_size(width, height)?),
+        };
+        Ok(Recognizer::new(
+            source,
+            self.model.clone(),
+            self.options.clone(),
+        ))
+    }
+}
+
+fn get_solver_options(args: &Args) -> Result<SolverOptions, MyError> {
+    let weights = match &args.weights {
+        Some(path) => HeuristicWeights::load(path)?,
+        None => HeuristicWeights::default(),
+    };
+    let score_rules = match &args.score_rules {
+        Some(path) => ScoreRules::load(path)?,
+        None => ScoreRules::default(),
+    };
+    // the target is only read when the board is solved for it
+    let uses_target =
+        args.method == SolvingMethods::Method9 || args.objectives.0.contains(&Objective::Target);
+    let target = match &args.target {
+        _ if !uses_target => None,
+        Some(path) => Some(TargetSpec::load(path)?),
+        None if args.method == SolvingMethods::Method9 && Path::new(TARGET_FILE).exists() => {
+            Some(TargetSpec::load(Path::new(TARGET_FILE))?)
+        }
+        None => return Err(MyError::MissingTarget),
+    };
+    Ok(SolverOptions {
+        weights,
+        objectives: args.objectives.clone(),
+        score_rules,
+        target,
+        time_limit: get_duration(args.time_limit)?,
+    })
+}
+
 fn main() {
-    let found_image_files = get_image_files();
+    let argument_options = Args::from_args();
+    // only commands which solve boards read the weights, score rules and target
+    let solver_options = match &argument_options.command {
+        Some(Command::DetectGrid { .. })
+        | Some(Command::Calibrate { .. })
+        | Some(Command::Render { .. })
+        | Some(Command::Video { .. })
+        | Some(Command::MarkTiles { .. }) => SolverOptions::default(),
+        _ => match get_solver_options(&argument_options) {
+            Ok(options) => options,
+            Err(error) => {
+                println!("Unable to load solver options. Error: {}", error.report());
+                return;
+            }
+        },
+    };
+    let geometry = match GeometrySettings::from_args(&argument_options) {
+        Ok(geometry) => geometry,
+        Err(error) => {
+            println!("Unable to load grid geometry. Error: {}", error.report());
+            return;
+        }
+    };
+    let colors = match ColorSettings::from_args(&argument_options) {
+        Ok(colors) => colors,
+        Err(error) => {
+            println!("Unable to load tile colors. Error: {}", error.report());
+            return;
+        }
+    };
+
+    match &argument_options.command {
+        Some(Command::Tune {
+            directory,
+            method,
+            strategy,
+            iterations,
+            output,
+        }) => {
+            if let Err(error) = run_tune(
+                directory.clone(),
+                method.clone(),
+                strategy.clone(),
+                *iterations,
+                output.clone(),
+                &solver_options,
+            ) {
+                println!("Failed to tune weights. Error: {}", error.report());
+            }
+            return;
+        }
+        Some(Command::Hint { board, count, time }) => {
+            if let Err(error) = run_hint(
+                board,
+                *count,
+                *time,
+                &argument_options.method,
+                &solver_options,
+                argument_options.map_format,
+                &geometry,
+                &colors,
+            ) {
+                println!("Failed to find hints. Error: {}", error.report());
+            }
+            return;
+        }
+        Some(Command::DetectGrid { image, output }) => {
+            if let Err(error) = run_detect_grid(image, output) {
+                println!("Failed to detect the grid. Error: {}", error.report());
+            }
+            return;
+        }
+        Some(Command::Calibrate { image, map, output }) => {
+            if let Err(error) = run_calibrate(image, map, output, &geometry, &colors) {
+                println!("Failed to calibrate tile colors. Error: {}", error.report());
+            }
+            return;
+        }
+        Some(Command::Render {
+            map,
+            output,
+            width,
+            height,
+            noise,
+            brightness,
+            jpeg_quality,
+            cursor,
+            seed,
+        }) => {
+            let options = RenderOptions {
+                width: *width,
+                height: *height,
+                noise: *noise,
+                brightness: *brightness,
+                jpeg_quality: *jpeg_quality,
+                cursor: *cursor,
+                seed: *seed,
+            };
+            if let Err(error) = run_render(map, output, &options, &geometry, &colors) {
+                println!("Failed to render the board. Error: {}", error.report());
+            }
+            return;
+        }
+        Some(Command::Video {
+            input,
+            width,
+            height,
+            stable_frames,
+            output,
+        }) => {
+            if let Err(error) = run_video(
+                input,
+                (*width, *height),
+                *stable_frames,
+                output,
+                argument_options.map_format,
+                &geometry,
+                &colors,
+            ) {
+                println!(
+                    "Failed to find a board in the frames. Error: {}",
+                    error.report()
+                );
+            }
+            return;
+        }
+        Some(Command::Replay {
+            boards,
+            max_clicks,
+            output,
+        }) => {
+            if let Err(error) = run_replay(
+                boards,
+                *max_clicks,
+                output,
+                &argument_options.method,
+                &solver_options,
+                &geometry,
+                &colors,
+            ) {
+                println!("Failed to replay the game. Error: {}", error.report());
+            }
+            return;
+        }
+        Some(Command::MarkTiles { image, output }) => {
+            if let Err(error) = run_mark_tiles(image, output, &geometry) {
+                println!(
+                    "Failed to write the script to mark tiles. Error: {}",
+                    error.report()
+                );
+            }
+            return;
+        }
+        None => (),
+    }
+
+    let found_image_files = match get_inputs(&argument_options.images) {
+        Ok(found_image_files) => found_image_files,
+        Err(error) => {
+            println!("Unable to find images. Error: {}", error.report());
+            return;
+        }
+    };
     if found_image_files.is_empty() {
-        println!("No PNG images found.");
+        println!("No images found.");
         return;
     }
 
-    let argument_options = Args::from_args();
     for image in found_image_files {
         println!(
             "Processing image {:?}...",
             image
+                .path()
                 .file_name()
                 .unwrap_or_else(|| std::ffi::OsStr::new("???"))
         );
-        match get_monolith_map(&image, argument_options.generate_map) {
-            Ok(map) => match write_solving_steps(&image, map, argument_options.method.clone()) {
-                Ok(_) => println!("Successfully finished processing file."),
-                Err(error) => println!("Failed to write solving steps. Error: {:?}", error),
-            },
-            Err(error) => println!("Unable to process image. Error: {:?}", error),
+        match get_monolith_map(
+            &image,
+            argument_options.generate_map,
+            argument_options.report,
+            argument_options.debug_image,
+            argument_options.map_format,
+            &geometry,
+            &colors,
+        ) {
+            Ok((map, recognised_geometry)) => {
+                // the recognition already resolved the grid, `detect` doesn't run twice
+                let image_geometry = match recognised_geometry
+                    .map(Ok)
+                    .unwrap_or_else(|| geometry.resolve_for_input(&image))
+                {
+                    Ok(image_geometry) => image_geometry,
+                    Err(error) => {
+                        println!(
+                            "Unable to match the grid to the image. Error: {}",
+                            error.report()
+                        );
+                        continue;
+                    }
+                };
+                match write_solving_steps(
+                    image.path(),
+                    map,
+                    argument_options.method.clone(),
+                    &solver_options,
+                    &image_geometry,
+                ) {
+                    Ok(_) => println!("Successfully finished processing file."),
+                    Err(error) => {
+                        println!("Failed to write solving steps. Error: {}", error.report())
+                    }
+                }
+            }
+            Err(error) => println!("Unable to process image. Error: {}", error.report()),
         }
     }
 }
@@ -0,0 +1,353 @@
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+
+use crate::errors::{MyError, WithPath};
+use crate::map::MAX_TILE;
+
+/// File which is picked up from the current directory if no other calibration is given.
+pub const DEFAULT_CALIBRATION_FILE: &str = "calibration.json";
+/// Smallest tolerance of a calibrated channel, so a perfectly uniform sample still
+/// leaves room for noise.
+const MIN_TOLERANCE: f64 = 6.0;
+/// Calibrated tolerance in standard deviations of the samples.
+const TOLERANCE_DEVIATIONS: f64 = 4.0;
+/// Approximate color of a cleared cell on a 1920x1080 screenshot without any filters.
+const BACKGROUND_COLOR: [f64; 3] = [28.0, 26.0, 32.0];
+/// Tolerance of the default background color, anything this dark is a cleared cell.
+const BACKGROUND_TOLERANCE: f64 = 40.0;
+
+pub type Color = (u8, u8, u8);
+
+/// Expected color of one tile type, or of cleared cells for type 0.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TileColor {
+    pub tile_type: u8,
+    /// Average `[r, g, b]` of this tile type.
+    pub centroid: [f64; 3],
+    /// Largest accepted difference from the centroid, per channel.
+    pub tolerance: [f64; 3],
+}
+
+impl TileColor {
+    /// Distance from the centroid in tolerances and whether the color is within the
+    /// tolerance on every channel.
+    fn get_distance(&self, color: Color) -> (f64, bool) {
+        let color = [f64::from(color.0), f64::from(color.1), f64::from(color.2)];
+        let mut distance = 0.0;
+        let mut inside = true;
+        for ((value, centroid), tolerance) in color.iter().zip(&self.centroid).zip(&self.tolerance)
+        {
+            let difference = (value - centroid).abs();
+            if difference >= *tolerance {
+                inside = false;
+            }
+            distance += (difference / tolerance).powi(2);
+        }
+        (distance.sqrt(), inside)
+    }
+}
+
+/// Colors of the tile types on screenshots.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ColorModel {
+    pub colors: Vec<TileColor>,
+    /// Color of cleared cells, so a board can be recognised at any point of a game.
+    #[serde(default = "default_background")]
+    pub background: TileColor,
+}
+
+fn default_background() -> TileColor {
+    TileColor {
+        tile_type: 0,
+        centroid: BACKGROUND_COLOR,
+        tolerance: [BACKGROUND_TOLERANCE; 3],
+    }
+}
+
+/// Centroid and tolerance of the colors.
+fn get_tile_color(tile_type: u8, values: &[[f64; 3]]) -> TileColor {
+    let count = values.len() as f64;
+    let mut centroid = [0.0; 3];
+    let mut tolerance = [0.0; 3];
+    for channel in 0..3 {
+        centroid[channel] = values.iter().map(|x| x[channel]).sum::<f64>() / count;
+        let variance = values
+            .iter()
+            .map(|x| (x[channel] - centroid[channel]).powi(2))
+            .sum::<f64>()
+            / count;
+        let max_difference = values
+            .iter()
+            .map(|x| (x[channel] - centroid[channel]).abs())
+            .fold(0.0, f64::max);
+        tolerance[channel] = (variance.sqrt() * TOLERANCE_DEVIATIONS)
+            .max(max_difference + 1.0)
+            .max(MIN_TOLERANCE);
+    }
+    TileColor {
+        tile_type,
+        centroid,
+        tolerance,
+    }
+}
+
+/// Colors of a 1920x1080 screenshot without any filters, with ±15% tolerance.
+impl Default for ColorModel {
+    fn default() -> Self {
+        let colors = [
+            (1, [189.0, 187.0, 187.0]),
+            (2, [236.0, 145.0, 187.0]),
+            (3, [211.0, 171.0, 110.0]),
+            (4, [99.0, 166.0, 184.0]),
+        ];
+        ColorModel {
+            colors: colors
+                .iter()
+                .map(|(tile_type, centroid)| TileColor {
+                    tile_type: *tile_type,
+                    centroid: *centroid,
+                    tolerance: [
+                        (centroid[0] * 0.15).floor(),
+                        (centroid[1] * 0.15).floor(),
+                        (centroid[2] * 0.15).floor(),
+                    ],
+                })
+                .collect(),
+            background: default_background(),
+        }
+    }
+}
+
+impl ColorModel {
+    /// Refuses colors of unknown tile types and tolerances which match nothing.
+    fn validated(self) -> Result<ColorModel, MyError> {
+        for color in self.colors.iter().chain(Some(&self.background)) {
+            if color.tile_type > MAX_TILE {
+                return Err(MyError::Calibration(format!(
+                    "unknown tile type {}",
+                    color.tile_type
+                )));
+            }
+            if !color.tolerance.iter().all(|x| *x > 0.0) {
+                return Err(MyError::Calibration(format!(
+                    "the tolerance {:?} of tile type {} isn't positive",
+                    color.tolerance, color.tile_type
+                )));
+            }
+        }
+        Ok(self)
+    }
+
+    pub fn from_content(content: &str) -> Result<ColorModel, MyError> {
+        serde_json::from_str::<ColorModel>(content)?.validated()
+    }
+
+    pub fn load(path: &Path) -> Result<ColorModel, MyError> {
+        let mut file = std::fs::File::open(path).with_path(path)?;
+        let mut buffer = String::new();
+        file.read_to_string(&mut buffer).with_path(path)?;
+        ColorModel::from_content(&buffer).with_path(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), MyError> {
+        let file = std::fs::File::create(path).with_path(path)?;
+        serde_json::to_writer_pretty(file, self).with_path(path)
+    }
+
+    /// Loads the calibration from the current directory if there is one.
+    pub fn load_default() -> Result<ColorModel, MyError> {
+        let path = Path::new(DEFAULT_CALIBRATION_FILE);
+        if path.exists() {
+            ColorModel::load(path)
+        } else {
+            Ok(ColorModel::default())
+        }
+    }
+
+    /// Computes centroids and tolerances from tile colors with known types. Every tile
+    /// type needs at least one sample, samples of type 0 (cleared cells) replace the
+    /// default background color if there are any.
+    pub fn calibrate(samples: &[(Color, u8)]) -> Result<ColorModel, MyError> {
+        let get_values = |tile_type: u8| {
+            samples
+                .iter()
+                .filter(|x| x.1 == tile_type)
+                .map(|x| [f64::from((x.0).0), f64::from((x.0).1), f64::from((x.0).2)])
+                .collect::<Vec<_>>()
+        };
+
+        let mut colors = Vec::with_capacity(4);
+        for tile_type in 1..=4 {
+            let values = get_values(tile_type);
+            if values.is_empty() {
+                return Err(MyError::Calibration(format!(
+                    "no samples of tile type {}",
+                    tile_type
+                )));
+            }
+            colors.push(get_tile_color(tile_type, &values));
+        }
+        let background = get_values(0);
+        let background = if background.is_empty() {
+            default_background()
+        } else {
+            get_tile_color(0, &background)
+        };
+        Ok(ColorModel { colors, background })
+    }
+
+    /// Closest color (tile type or background) which has the color within its tolerance.
+    fn find_match(&self, color: Color) -> Option<(&TileColor, f64)> {
+        let mut best: Option<(&TileColor, f64)> = None;
+        for tile_color in self.colors.iter().chain(std::iter::once(&self.background)) {
+            let (distance, inside) = tile_color.get_distance(color);
+            if inside && best.map_or(true, |best| distance < best.1) {
+                best = Some((tile_color, distance));
+            }
+        }
+        best
+    }
+
+    /// Tile type of the color, 0 for cleared cells and colors which don't match any type.
+    /// If the color is within the tolerance of several types, the closest one wins.
+    pub fn classify(&self, color: Color) -> u8 {
+        self.find_match(color).map_or(0, |x| x.0.tile_type)
+    }
+
+    /// Tile type of the color like `classify` along with a confidence from 0 to 1.
+    /// A tile is confident if it's near the centroid of its color and much closer to it
+    /// than to any other. Colors which match neither a tile type nor the background
+    /// have no confidence at all.
+    pub fn classify_with_confidence(&self, color: Color) -> (u8, f64) {
+        let (matched, own) = match self.find_match(color) {
+            Some(x) => x,
+            None => return (0, 0.0),
+        };
+        let nearest_other = self
+            .colors
+            .iter()
+            .chain(std::iter::once(&self.background))
+            .filter(|x| x.tile_type != matched.tile_type)
+            .map(|x| x.get_distance(color).0)
+            .fold(f64::INFINITY, f64::min);
+        // the distance within the tolerance on all three channels is below sqrt(3)
+        let centrality = 1.0 - own / 3.0_f64.sqrt();
+        let confidence = if nearest_other.is_infinite() {
+            centrality
+        } else {
+            centrality * (nearest_other - own) / (nearest_other + own).max(f64::EPSILON)
+        };
+        (matched.tile_type, confidence.clamp(0.0, 1.0))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ColorModel;
+    use crate::errors::MyError;
+
+    #[test]
+    fn test_default_model() {
+        let model = ColorModel::default();
+        assert_eq!(model.classify((189, 187, 187)), 1);
+        assert_eq!(model.classify((240, 150, 180)), 2);
+        assert_eq!(model.classify((211, 171, 110)), 3);
+        assert_eq!(model.classify((99, 166, 184)), 4);
+        assert_eq!(model.classify((20, 20, 20)), 0);
+        // 15% of 99 is 14, the window is exclusive
+        assert_eq!(model.classify((113, 166, 184)), 0);
+        assert_eq!(model.classify((112, 166, 184)), 4);
+    }
+
+    #[test]
+    fn test_confidence() {
+        let model = ColorModel::default();
+        let (tile_type, confidence) = model.classify_with_confidence((189, 187, 187));
+        assert_eq!((tile_type, confidence), (1, 1.0));
+        let (tile_type, confidence) = model.classify_with_confidence((28, 26, 32));
+        assert_eq!((tile_type, confidence), (0, 1.0));
+        let (tile_type, confidence) = model.classify_with_confidence((20, 20, 20));
+        assert_eq!(tile_type, 0);
+        assert!(confidence > 0.5);
+
+        // just outside of the tolerance of type 4, but not a cleared cell either
+        let (tile_type, confidence) = model.classify_with_confidence((113, 166, 184));
+        assert_eq!((tile_type, confidence), (0, 0.0));
+        // near the edge of the tolerance of type 1, towards type 4
+        let (tile_type, confidence) = model.classify_with_confidence((162, 178, 186));
+        assert_eq!(tile_type, 1);
+        assert!(confidence < 0.5);
+    }
+
+    #[test]
+    fn test_calibration() {
+        // same board with a dark color filter
+        let samples = vec![
+            ((140, 139, 139), 1),
+            ((144, 141, 139), 1),
+            ((176, 108, 139), 2),
+            ((158, 128, 82), 3),
+            ((160, 126, 80), 3),
+            ((74, 124, 137), 4),
+            ((20, 20, 20), 0),
+        ];
+        let default = ColorModel::default();
+        assert_eq!(default.classify((142, 140, 139)), 0);
+
+        let model = ColorModel::calibrate(&samples).unwrap();
+        for sample in &samples {
+            assert_eq!(model.classify(sample.0), sample.1);
+        }
+        assert_eq!(model.classify((142, 140, 139)), 1);
+        assert_eq!(model.colors[0].centroid, [142.0, 140.0, 139.0]);
+        assert_eq!(model.background.centroid, [20.0, 20.0, 20.0]);
+        assert_eq!(
+            ColorModel::calibrate(&samples[..6]).unwrap().background,
+            ColorModel::default().background
+        );
+
+        assert!(ColorModel::calibrate(&samples[..4]).is_err());
+    }
+
+    #[test]
+    fn test_load_without_background() {
+        let model = serde_json::from_str::<ColorModel>(
+            r#"{"colors": [{"tile_type": 1, "centroid": [1.0, 2.0, 3.0], "tolerance": [4.0, 5.0, 6.0]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(model.colors.len(), 1);
+        assert_eq!(model.background, ColorModel::default().background);
+    }
+
+    #[test]
+    fn test_invalid_model() {
+        let model = r#"{"colors": [{"tile_type": 1, "centroid": [1.0, 2.0, 3.0], "tolerance": [4.0, 5.0, 6.0]}]}"#;
+        assert!(ColorModel::from_content(model).is_ok());
+        assert!(matches!(
+            ColorModel::from_content(&model.replace("\"tile_type\": 1", "\"tile_type\": 5")),
+            Err(MyError::Calibration(_))
+        ));
+        assert!(matches!(
+            ColorModel::from_content(&model.replace("5.0", "0.0")),
+            Err(MyError::Calibration(_))
+        ));
+        assert!(matches!(
+            ColorModel::from_content(&model.replace("5.0", "-5.0")),
+            Err(MyError::Calibration(_))
+        ));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!(
+            "monolith_solver_test_calibration_{}.json",
+            std::process::id()
+        ));
+        let model = ColorModel::default();
+        model.save(&path).unwrap();
+        let loaded = ColorModel::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), model);
+    }
+}
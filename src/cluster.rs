@@ -0,0 +1,350 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::calibration::{Color, ColorModel};
+
+/// Cleared cells are told apart from tiles if they are at most this bright relative to the tiles.
+const MAX_BACKGROUND_LIGHTNESS: f64 = 0.5;
+/// k-means stops after this many iterations even if the clusters still move.
+const MAX_ITERATIONS: usize = 100;
+
+type Lab = [f64; 3];
+
+/// Recognised type of one tile.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct TileRecognition {
+    /// Tile type, 0 for empty cells and unknown colors.
+    pub tile_type: u8,
+    /// From 0 (could be anything) to 1 (certain).
+    pub confidence: f64,
+}
+
+/// How average tile colors are turned into tile types.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TileClassifier {
+    /// Every tile is matched against the centroids and tolerances of the color model.
+    Colors,
+    /// All tiles of the board are clustered in CIELAB and the clusters are mapped to the
+    /// nearest color of the model, so the brightness of the screenshot doesn't matter.
+    Clusters,
+}
+
+impl FromStr for TileClassifier {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "colors" => Ok(Self::Colors),
+            "clusters" => Ok(Self::Clusters),
+            _ => Err(format!("Unknown tile classifier '{}'", s)),
+        }
+    }
+}
+
+impl TileClassifier {
+    /// Tile types of the colors with their confidence.
+    pub fn classify(&self, colors: &[Color], model: &ColorModel) -> Vec<TileRecognition> {
+        match self {
+            TileClassifier::Colors => colors
+                .iter()
+                .map(|x| {
+                    let (tile_type, confidence) = model.classify_with_confidence(*x);
+                    TileRecognition {
+                        tile_type,
+                        confidence,
+                    }
+                })
+                .collect(),
+            TileClassifier::Clusters => classify_clusters(colors, model),
+        }
+    }
+}
+
+fn to_linear(value: f64) -> f64 {
+    let value = value.clamp(0.0, 255.0) / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts an sRGB color to CIELAB with the D65 white point.
+fn to_lab(color: [f64; 3]) -> Lab {
+    let (r, g, b) = (
+        to_linear(color[0]),
+        to_linear(color[1]),
+        to_linear(color[2]),
+    );
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.950_47;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.088_83;
+
+    let f = |t: f64| {
+        if t > 216.0 / 24389.0 {
+            t.cbrt()
+        } else {
+            (24389.0 / 27.0 * t + 16.0) / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+fn get_distance(first: &Lab, second: &Lab) -> f64 {
+    first
+        .iter()
+        .zip(second)
+        .map(|(a, b)| (a - b).powi(2))
+        .sum::<f64>()
+}
+
+fn get_nearest(point: &Lab, centroids: &[Lab]) -> usize {
+    let mut nearest = 0;
+    for (index, centroid) in centroids.iter().enumerate() {
+        if get_distance(point, centroid) < get_distance(point, &centroids[nearest]) {
+            nearest = index;
+        }
+    }
+    nearest
+}
+
+/// Lloyd's k-means starting from the given centroids. Returns the cluster of every point
+/// and the final centroids.
+fn k_means(points: &[Lab], mut centroids: Vec<Lab>) -> (Vec<usize>, Vec<Lab>) {
+    let mut clusters = vec![usize::MAX; points.len()];
+    for _ in 0..MAX_ITERATIONS {
+        let mut changed = false;
+        for (point, cluster) in points.iter().zip(clusters.iter_mut()) {
+            let nearest = get_nearest(point, &centroids);
+            if *cluster != nearest {
+                *cluster = nearest;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+
+        for (index, centroid) in centroids.iter_mut().enumerate() {
+            let members = points
+                .iter()
+                .zip(&clusters)
+                .filter(|x| *x.1 == index)
+                .map(|x| x.0)
+                .collect::<Vec<_>>();
+            // a cluster without members keeps its centroid
+            if members.is_empty() {
+                continue;
+            }
+            for channel in 0..3 {
+                centroid[channel] =
+                    members.iter().map(|x| x[channel]).sum::<f64>() / members.len() as f64;
+            }
+        }
+    }
+    (clusters, centroids)
+}
+
+fn get_median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    values[values.len() / 2]
+}
+
+/// Median lightness of the tiles. Cleared cells are much darker than any tile, so on a
+/// partially played board the points are split into dark and bright ones first.
+fn get_tiles_lightness(points: &[Lab]) -> f64 {
+    let lightness = points.iter().map(|x| x[0]).collect::<Vec<_>>();
+    let mut dark = lightness.iter().cloned().fold(f64::INFINITY, f64::min);
+    let mut bright = lightness.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    for _ in 0..MAX_ITERATIONS {
+        let threshold = (dark + bright) / 2.0;
+        let mean = |values: Vec<f64>| values.iter().sum::<f64>() / values.len().max(1) as f64;
+        let new_dark = mean(
+            lightness
+                .iter()
+                .cloned()
+                .filter(|x| *x <= threshold)
+                .collect(),
+        );
+        let new_bright = mean(
+            lightness
+                .iter()
+                .cloned()
+                .filter(|x| *x > threshold)
+                .collect(),
+        );
+        if (new_dark, new_bright) == (dark, bright) {
+            break;
+        }
+        dark = new_dark;
+        bright = new_bright;
+    }
+
+    let mut tiles = if dark <= bright * MAX_BACKGROUND_LIGHTNESS {
+        let threshold = (dark + bright) / 2.0;
+        lightness.into_iter().filter(|x| *x > threshold).collect()
+    } else {
+        lightness
+    };
+    get_median(&mut tiles)
+}
+
+/// Clusters the colors with k-means (one cluster per tile type and one for cleared cells)
+/// and maps every cluster to the nearest color of the model. The model colors are scaled
+/// to the median lightness of the tiles first, so darker or brighter screenshots
+/// still match. Tiles between two clusters of different types get a low confidence.
+pub fn classify_clusters(colors: &[Color], model: &ColorModel) -> Vec<TileRecognition> {
+    if colors.is_empty() {
+        return Vec::new();
+    }
+    let points = colors
+        .iter()
+        .map(|x| to_lab([f64::from(x.0), f64::from(x.1), f64::from(x.2)]))
+        .collect::<Vec<_>>();
+
+    let mut references = model
+        .colors
+        .iter()
+        .map(|x| (x.tile_type, to_lab(x.centroid)))
+        .collect::<Vec<_>>();
+    let tiles_lightness =
+        references.iter().map(|x| (x.1)[0]).sum::<f64>() / references.len().max(1) as f64;
+    references.push((0, to_lab(model.background.centroid)));
+    let scale = get_tiles_lightness(&points) / tiles_lightness;
+    for reference in references.iter_mut() {
+        for channel in reference.1.iter_mut() {
+            *channel *= scale;
+        }
+    }
+
+    let reference_colors = references.iter().map(|x| x.1).collect::<Vec<_>>();
+    let (clusters, centroids) = k_means(&points, reference_colors.clone());
+    let cluster_types = centroids
+        .iter()
+        .enumerate()
+        .map(|(index, centroid)| {
+            if clusters.contains(&index) {
+                Some(references[get_nearest(centroid, &reference_colors)].0)
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    points
+        .iter()
+        .zip(clusters)
+        .map(|(point, cluster)| {
+            let tile_type = cluster_types[cluster].unwrap_or(0);
+            let own = get_distance(point, &centroids[cluster]).sqrt();
+            let nearest_other = centroids
+                .iter()
+                .zip(&cluster_types)
+                .filter(|x| x.1.map_or(false, |x| x != tile_type))
+                .map(|x| get_distance(point, x.0).sqrt())
+                .fold(f64::INFINITY, f64::min);
+            let confidence = if nearest_other.is_infinite() {
+                1.0
+            } else {
+                (nearest_other - own) / (nearest_other + own).max(f64::EPSILON)
+            };
+            TileRecognition {
+                tile_type,
+                confidence: confidence.clamp(0.0, 1.0),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{classify_clusters, TileClassifier, TileRecognition};
+    use crate::calibration::ColorModel;
+
+    fn get_types(recognitions: &[TileRecognition]) -> Vec<u8> {
+        recognitions.iter().map(|x| x.tile_type).collect()
+    }
+
+    fn get_board(cleared: usize) -> (Vec<(u8, u8, u8)>, Vec<u8>) {
+        let palette = [
+            (28, 26, 32),
+            (189, 187, 187),
+            (236, 145, 187),
+            (211, 171, 110),
+            (99, 166, 184),
+        ];
+        let mut colors = vec![];
+        let mut types = vec![];
+        for index in 0..242_usize {
+            // a few holes and the first `cleared` cells are empty, the rest cycles through
+            // the tile types
+            let tile_type = if index % 37 == 0 || index < cleared {
+                0
+            } else {
+                index % 4 + 1
+            };
+            let color = palette[tile_type];
+            let noise = (index % 5) as u8;
+            colors.push((color.0 - noise, color.1 + noise, color.2 - noise));
+            types.push(tile_type as u8);
+        }
+        (colors, types)
+    }
+
+    #[test]
+    fn test_classify_clusters() {
+        let (colors, types) = get_board(0);
+        let model = ColorModel::default();
+        let recognised = classify_clusters(&colors, &model);
+        assert_eq!(get_types(&recognised), types);
+        assert!(recognised.iter().all(|x| x.confidence > 0.5));
+        assert_eq!(
+            get_types(&TileClassifier::Colors.classify(&colors, &model)),
+            types
+        );
+    }
+
+    #[test]
+    fn test_classify_dark_board() {
+        let (colors, types) = get_board(0);
+        let colors = colors
+            .iter()
+            .map(|x| {
+                let darken = |value: u8| (f64::from(value) * 0.7) as u8;
+                (darken(x.0), darken(x.1), darken(x.2))
+            })
+            .collect::<Vec<_>>();
+        let model = ColorModel::default();
+        // fixed centroids don't recognise most of the tiles anymore
+        let recognised = get_types(&TileClassifier::Colors.classify(&colors, &model));
+        assert!(recognised.iter().zip(&types).filter(|x| x.0 != x.1).count() > 100);
+        assert_eq!(
+            get_types(&TileClassifier::Clusters.classify(&colors, &model)),
+            types
+        );
+    }
+
+    #[test]
+    fn test_classify_partially_played_board() {
+        let (colors, types) = get_board(180);
+        let model = ColorModel::default();
+        for classifier in &[TileClassifier::Colors, TileClassifier::Clusters] {
+            let recognised = classifier.classify(&colors, &model);
+            assert_eq!(get_types(&recognised), types);
+            assert!(recognised.iter().all(|x| x.confidence > 0.2));
+        }
+
+        let colors = colors
+            .iter()
+            .map(|x| {
+                let darken = |value: u8| (f64::from(value) * 0.7) as u8;
+                (darken(x.0), darken(x.1), darken(x.2))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            get_types(&TileClassifier::Clusters.classify(&colors, &model)),
+            types
+        );
+    }
+}
@@ -1,16 +1,19 @@
 use std::io::{BufWriter, Write};
-use std::path::PathBuf;
+use std::path::Path;
+use std::time::Instant;
 
-use crate::errors::MyError;
-use crate::map::{MonolithMap, SolvingMethods, Tile};
+use crate::errors::{MyError, WithPath};
+use crate::geometry::GridGeometry;
+use crate::map::{MonolithMap, SolverOptions, SolvingMethods, Tile};
+use crate::solution::Solution;
 
 const AHK_TEMPLATE: &str = r#"
 #SingleInstance Force
 
 makeCircle() {
     Gui New, +E0x20 +AlwaysOnTop +ToolWindow -Caption +HwndHWND
-    r := 120
-    thickness := 20
+    r := CIRCLE_SIZE_MARKER
+    thickness := CIRCLE_THICKNESS_MARKER
     transparency := 240
     color := 0x00CD00
 
@@ -34,8 +37,8 @@ loop {
     if WinActive("ahk_exe Dangan3Win.exe")
     {
         tile := tiles[step]
-        x := tile[1] * 80 + 80 - 20
-        y := tile[2] * 80 + 80 - 20
+        x := Round(tile[1] * TILE_SIZE_MARKER + ORIGIN_X_MARKER + (TILE_SIZE_MARKER - r) / 2)
+        y := Round(tile[2] * TILE_SIZE_MARKER + ORIGIN_Y_MARKER + (TILE_SIZE_MARKER - r) / 2)
         Gui %hCircle%:Show, X%x% Y%y% NoActivate
     }
     else
@@ -64,9 +67,60 @@ loop {
     Return
 "#;
 
+const MARK_TILES_TEMPLATE: &str = r#"
+#SingleInstance Force
+
+tiles := []
+
+~q::
+    output := ""
+    for k,v in tiles
+        output .= (k > 1 ? "," : "") . "[" . v[1] . "," . v[2] . "]"
+    FileDelete, TARGET_FILE_MARKER
+    FileAppend, [%output%], TARGET_FILE_MARKER
+
+    ExitApp
+    Return
+
+~Space::
+    MouseGetPos, x, y
+    tx := Floor((x - ORIGIN_X_MARKER) / TILE_SIZE_MARKER)
+    ty := Floor((y - ORIGIN_Y_MARKER) / TILE_SIZE_MARKER)
+    if (tx >= 0 && tx < COLUMNS_MARKER && ty >= 0 && ty < ROWS_MARKER)
+    {
+        tiles.push([tx, ty])
+    }
+    Return
+"#;
+
+/// Fills the overlay template with the solving steps and the circle position and size.
+fn create_script(steps: Vec<Tile>, geometry: &GridGeometry) -> String {
+    let circle_size = (geometry.tile_size * 1.5).round() as u32;
+    let circle_thickness = (geometry.tile_size / 4.0).round() as u32;
+    AHK_TEMPLATE
+        .replace("ARRAY_MARKER", &create_array_string(steps))
+        .replace("CIRCLE_SIZE_MARKER", &circle_size.to_string())
+        .replace("CIRCLE_THICKNESS_MARKER", &circle_thickness.to_string())
+        .replace("TILE_SIZE_MARKER", &format!("{:.2}", geometry.tile_size))
+        .replace("ORIGIN_X_MARKER", &format!("{:.2}", geometry.origin_x))
+        .replace("ORIGIN_Y_MARKER", &format!("{:.2}", geometry.origin_y))
+}
+
+/// Fills the template of the script which saves the tiles under the cursor (Space) as a
+/// target pattern when it's closed (Q).
+fn create_mark_tiles_script(geometry: &GridGeometry, target_file: &str) -> String {
+    MARK_TILES_TEMPLATE
+        .replace("TARGET_FILE_MARKER", target_file)
+        .replace("TILE_SIZE_MARKER", &format!("{:.2}", geometry.tile_size))
+        .replace("ORIGIN_X_MARKER", &format!("{:.2}", geometry.origin_x))
+        .replace("ORIGIN_Y_MARKER", &format!("{:.2}", geometry.origin_y))
+        .replace("COLUMNS_MARKER", &geometry.columns.to_string())
+        .replace("ROWS_MARKER", &geometry.rows.to_string())
+}
+
 fn create_array_string(steps: Vec<Tile>) -> String {
     let mut result = String::with_capacity(300);
-    result.push_str("[");
+    result.push('[');
     result.push_str(
         &steps
             .into_iter()
@@ -74,7 +128,7 @@ fn create_array_string(steps: Vec<Tile>) -> String {
             .collect::<Vec<String>>()
             .join(", "),
     );
-    result.push_str("]");
+    result.push(']');
     result
 }
 
@@ -85,13 +139,46 @@ fn test_create_array_string() {
     assert_eq!(result, "[[1,2], [21,10], [0,5]]");
 }
 
+#[test]
+fn test_create_script() {
+    let script = create_script(vec![(1, 2)], &GridGeometry::default());
+    assert!(script.contains("tiles := [[1,2]]"));
+    assert!(script.contains("r := 120"));
+    assert!(script.contains("thickness := 20"));
+    assert!(script.contains("x := Round(tile[1] * 80.00 + 80.00 + (80.00 - r) / 2)"));
+    assert!(!script.contains("MARKER"));
+}
+
+#[test]
+fn test_create_mark_tiles_script() {
+    let geometry = GridGeometry::default().scaled(2.0);
+    let script = create_mark_tiles_script(&geometry, "tiles.txt");
+    assert!(script.contains("FileAppend, [%output%], tiles.txt"));
+    assert!(script.contains("tx := Floor((x - 160.00) / 160.00)"));
+    assert!(script.contains("tx < 22 && ty >= 0 && ty < 11"));
+    assert!(!script.contains("MARKER"));
+}
+
+/// Writes the script which marks the tiles of a target pattern, see
+/// `create_mark_tiles_script`.
+pub fn write_mark_tiles_script(
+    path: &Path,
+    geometry: &GridGeometry,
+    target_file: &str,
+) -> Result<(), MyError> {
+    let script = create_mark_tiles_script(geometry, target_file);
+    std::fs::write(path, script).with_path(path)
+}
+
 pub fn write_solving_steps(
-    image: &PathBuf,
+    image: &Path,
     map: MonolithMap,
     solve_method: SolvingMethods,
+    options: &SolverOptions,
+    geometry: &GridGeometry,
 ) -> Result<(), MyError> {
     let solver_filepath = {
-        let mut temp = image.clone();
+        let mut temp = image.to_path_buf();
         temp.set_extension("ahk");
         temp
     };
@@ -105,13 +192,20 @@ pub fn write_solving_steps(
     }
 
     println!("Solving the map (this may take a while).");
-    let steps = map.solve(solve_method);
+    let start = Instant::now();
+    let steps = solve_method.clone().solve_with(map.clone(), options)?;
     if steps.is_empty() {
         println!("Solver returned 0-step solution.");
         return Ok(());
     }
+    let solution = Solution::new(map, solve_method, options.clone(), &steps, start.elapsed());
+    println!(
+        "Solution takes {} clicks and scores {} points.",
+        steps.len(),
+        solution.score
+    );
 
-    let solver_file = std::fs::File::create(&solver_filepath)?;
+    let solver_file = std::fs::File::create(&solver_filepath).with_path(&solver_filepath)?;
 
     println!(
         "Writing solving steps to {:?}.",
@@ -120,12 +214,16 @@ pub fn write_solving_steps(
             .unwrap_or_else(|| std::ffi::OsStr::new("???"))
     );
     let mut writer = BufWriter::new(solver_file);
-    let steps_string = create_array_string(steps);
-    writer.write_all(
-        AHK_TEMPLATE
-            .replace("ARRAY_MARKER", &steps_string)
-            .as_bytes(),
-    )?;
-
-    Ok(())
+    writer
+        .write_all(create_script(steps, geometry).as_bytes())
+        .with_path(&solver_filepath)?;
+
+    let solution_filepath = image.with_extension("solution.json");
+    println!(
+        "Writing the solution to {:?}.",
+        solution_filepath
+            .file_name()
+            .unwrap_or_else(|| std::ffi::OsStr::new("???"))
+    );
+    solution.save(&solution_filepath)
 }
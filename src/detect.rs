@@ -0,0 +1,200 @@
+use image::DynamicImage;
+
+use crate::errors::MyError;
+use crate::geometry::GridGeometry;
+
+const COLUMNS: u32 = 22;
+const ROWS: u32 = 11;
+/// Smallest tile size worth looking for, in pixels.
+const MIN_TILE_SIZE: f64 = 12.0;
+/// Border width relative to the tile size, same as in the 1920x1080 layout.
+const BORDER_RATIO: f64 = 4.0 / 80.0;
+/// Tile edges are searched this far (relative to the tile size) from the expected grid line.
+const EDGE_TOLERANCE: f64 = 0.07;
+/// How much stronger the edges on the grid lines have to be than an average edge.
+const MIN_CONTRAST: f64 = 1.5;
+
+/// Best grid lines found along one axis.
+#[derive(Debug, Clone, Copy)]
+struct Comb {
+    origin: f64,
+    score: f64,
+}
+
+/// Finds the tile grid on a screenshot, so windowed and cropped screenshots
+/// can be recognised too. Tile edges produce peaks in the edge profiles of the
+/// image which repeat every tile, the grid is the comb of evenly spaced lines
+/// (23 vertical and 12 horizontal) which hits the strongest peaks.
+pub fn detect_grid(image: &DynamicImage) -> Result<GridGeometry, MyError> {
+    let (column_profile, row_profile) = get_edge_profiles(image);
+    let max_tile_size = (column_profile.len() as f64 / f64::from(COLUMNS))
+        .min(row_profile.len() as f64 / f64::from(ROWS));
+    if max_tile_size < MIN_TILE_SIZE {
+        return Err(MyError::GridNotFound);
+    }
+
+    let search = |from: f64, to: f64, step: f64| {
+        let mut best: Option<(f64, Comb, Comb)> = None;
+        let mut tile_size = from;
+        while tile_size <= to {
+            let x = find_comb(&column_profile, tile_size, COLUMNS);
+            let y = find_comb(&row_profile, tile_size, ROWS);
+            if let (Some(x), Some(y)) = (x, y) {
+                if best.map_or(true, |best| x.score + y.score > best.1.score + best.2.score) {
+                    best = Some((tile_size, x, y));
+                }
+            }
+            tile_size += step;
+        }
+        best
+    };
+
+    let (coarse_size, _, _) =
+        search(MIN_TILE_SIZE, max_tile_size, 1.0).ok_or(MyError::GridNotFound)?;
+    let (tile_size, x, y) = search(
+        (coarse_size - 1.0).max(MIN_TILE_SIZE),
+        (coarse_size + 1.0).min(max_tile_size),
+        0.1,
+    )
+    .ok_or(MyError::GridNotFound)?;
+
+    let contrast =
+        (x.score / get_average(&column_profile)).min(y.score / get_average(&row_profile));
+    if contrast.is_nan() || contrast < MIN_CONTRAST {
+        return Err(MyError::GridNotFound);
+    }
+    Ok(GridGeometry {
+        origin_x: x.origin,
+        origin_y: y.origin,
+        tile_size,
+        border_width: tile_size * BORDER_RATIO,
+        columns: COLUMNS,
+        rows: ROWS,
+    })
+}
+
+/// Mean brightness change between neighbouring pixels, for every column and every row.
+fn get_edge_profiles(image: &DynamicImage) -> (Vec<f64>, Vec<f64>) {
+    let image = image.to_luma();
+    let (width, height) = image.dimensions();
+    let mut columns = vec![0.0; width as usize];
+    let mut rows = vec![0.0; height as usize];
+    for y in 0..height {
+        for x in 0..width {
+            let value = f64::from(image.get_pixel(x, y)[0]);
+            if x + 1 < width {
+                columns[x as usize] += (f64::from(image.get_pixel(x + 1, y)[0]) - value).abs();
+            }
+            if y + 1 < height {
+                rows[y as usize] += (f64::from(image.get_pixel(x, y + 1)[0]) - value).abs();
+            }
+        }
+    }
+    for value in columns.iter_mut() {
+        *value /= f64::from(height);
+    }
+    for value in rows.iter_mut() {
+        *value /= f64::from(width);
+    }
+    (columns, rows)
+}
+
+fn get_average(profile: &[f64]) -> f64 {
+    profile.iter().sum::<f64>() / profile.len().max(1) as f64
+}
+
+/// Best position of `tiles + 1` lines which are `tile_size` apart, scored by the
+/// mean of the strongest edge near every line.
+fn find_comb(profile: &[f64], tile_size: f64, tiles: u32) -> Option<Comb> {
+    let length = profile.len();
+    let span = tile_size * f64::from(tiles);
+    if span >= length as f64 {
+        return None;
+    }
+
+    // strongest edge within the tolerance of every pixel
+    let tolerance = (tile_size * EDGE_TOLERANCE).round().max(1.0) as usize;
+    let peaks = (0..length)
+        .map(|i| {
+            let from = i.saturating_sub(tolerance);
+            let to = (i + tolerance).min(length - 1);
+            profile[from..=to].iter().cloned().fold(0.0, f64::max)
+        })
+        .collect::<Vec<_>>();
+
+    let mut best: Option<Comb> = None;
+    for origin in 0..(length as f64 - span) as usize {
+        let score = (0..=tiles)
+            .map(|k| {
+                peaks[((origin as f64 + f64::from(k) * tile_size).round() as usize).min(length - 1)]
+            })
+            .sum::<f64>()
+            / f64::from(tiles + 1);
+        if best.map_or(true, |best| score > best.score) {
+            best = Some(Comb {
+                origin: origin as f64,
+                score,
+            });
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod test {
+    use super::detect_grid;
+    use image::{DynamicImage, Rgb, RgbImage};
+
+    const COLORS: [[u8; 3]; 4] = [[189, 187, 187], [236, 145, 187], [211, 171, 110], [99, 166, 184]];
+
+    /// Draws a board with the tile grid at the given position.
+    fn draw_board(width: u32, height: u32, origin: (u32, u32), tile_size: u32) -> DynamicImage {
+        let border = (tile_size / 20).max(1);
+        let mut image = RgbImage::from_pixel(width, height, Rgb([30, 20, 40]));
+        for tile_x in 0..22 {
+            for tile_y in 0..11 {
+                let color = Rgb(COLORS[(tile_x * 7 + tile_y * 3) as usize % 4]);
+                let start_x = origin.0 + tile_x * tile_size;
+                let start_y = origin.1 + tile_y * tile_size;
+                for x in start_x + border..start_x + tile_size - border {
+                    for y in start_y + border..start_y + tile_size - border {
+                        image.put_pixel(x, y, color);
+                    }
+                }
+            }
+        }
+        DynamicImage::ImageRgb8(image)
+    }
+
+    #[test]
+    fn test_detect_cropped_board() {
+        let image = draw_board(700, 400, (37, 61), 28);
+        let geometry = detect_grid(&image).unwrap();
+        assert!((geometry.origin_x - 37.0).abs() <= 1.0);
+        assert!((geometry.origin_y - 61.0).abs() <= 1.0);
+        assert!((geometry.tile_size - 28.0).abs() <= 0.2);
+        assert!(geometry.validate(700, 400).is_ok());
+    }
+
+    #[test]
+    fn test_detect_board_with_overlay() {
+        let mut image = draw_board(800, 450, (90, 80), 30).to_rgb();
+        for x in 150..400 {
+            for y in 120..180 {
+                image.put_pixel(x, y, Rgb([255, 255, 255]));
+            }
+        }
+        let geometry = detect_grid(&DynamicImage::ImageRgb8(image)).unwrap();
+        assert!((geometry.origin_x - 90.0).abs() <= 1.0);
+        assert!((geometry.origin_y - 80.0).abs() <= 1.0);
+        assert!((geometry.tile_size - 30.0).abs() <= 0.2);
+    }
+
+    #[test]
+    fn test_no_grid() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(640, 360, Rgb([50, 50, 50])));
+        assert!(detect_grid(&image).is_err());
+        let tiny = DynamicImage::ImageRgb8(RgbImage::from_pixel(100, 50, Rgb([50, 50, 50])));
+        assert!(detect_grid(&tiny).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::errors::MyError;
+use crate::map::{MonolithMap, MAX_X, MAX_Y};
+
+/// A color counts as present on the board if it has at least this many tiles,
+/// so a single misrecognised tile doesn't add one.
+const MIN_TILES_PER_COLOR: usize = 2;
+/// Boards with at least this many tiles have every color of their difficulty.
+const MIN_TILES_FOR_DETECTION: usize = 150;
+
+/// Difficulty of the minigame, which sets the number of tile colors.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    Gentle,
+    Kind,
+    Mean,
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "gentle" => Ok(Self::Gentle),
+            "kind" => Ok(Self::Kind),
+            "mean" => Ok(Self::Mean),
+            _ => Err(format!("Unknown difficulty '{}'", s)),
+        }
+    }
+}
+
+impl Difficulty {
+    /// Number of tile colors on boards of this difficulty.
+    pub fn color_count(self) -> usize {
+        match self {
+            Difficulty::Gentle => 2,
+            Difficulty::Kind => 3,
+            Difficulty::Mean => 4,
+        }
+    }
+
+    /// Difficulty with the number of colors on the board.
+    pub fn detect(map: &MonolithMap) -> Difficulty {
+        match get_color_count(map) {
+            0..=2 => Difficulty::Gentle,
+            3 => Difficulty::Kind,
+            _ => Difficulty::Mean,
+        }
+    }
+
+    /// Checks that the board can be of this difficulty: it can't have more colors, and
+    /// a nearly full board can't have fewer colors either. Played boards may have lost
+    /// some colors, so they aren't refused for that.
+    pub fn check(self, map: &MonolithMap) -> Result<(), MyError> {
+        let colors = get_color_count(map);
+        let tiles = map.get_all_tiles_count() as usize;
+        if colors > self.color_count()
+            || (colors < self.color_count() && tiles >= MIN_TILES_FOR_DETECTION)
+        {
+            return Err(MyError::DifficultyMismatch {
+                expected: self,
+                found: Difficulty::detect(map),
+            });
+        }
+        Ok(())
+    }
+}
+
+fn get_color_count(map: &MonolithMap) -> usize {
+    let mut counts = [0; 5];
+    for x in 0..MAX_X {
+        for y in 0..MAX_Y {
+            counts[map.get(x, y) as usize] += 1;
+        }
+    }
+    counts[1..]
+        .iter()
+        .filter(|x| **x >= MIN_TILES_PER_COLOR)
+        .count()
+}
+
+#[cfg(test)]
+mod test {
+    use super::Difficulty;
+    use crate::map::{MonolithMap, MAX_X, MAX_Y};
+
+    fn get_map(colors: usize, tiles: usize) -> MonolithMap {
+        MonolithMap::from_fn(0..MAX_Y, |x, y| {
+            let index = y * MAX_X + x;
+            if index < tiles {
+                index % colors + 1
+            } else {
+                0
+            }
+        })
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Difficulty::detect(&get_map(4, 242)), Difficulty::Mean);
+        assert_eq!(Difficulty::detect(&get_map(3, 242)), Difficulty::Kind);
+        assert_eq!(Difficulty::detect(&get_map(2, 242)), Difficulty::Gentle);
+        assert_eq!(
+            Difficulty::detect(&MonolithMap::default()),
+            Difficulty::Gentle
+        );
+
+        // a single tile of the fourth color is a recognition error
+        let mut map = get_map(3, 242);
+        map.set(0, MAX_Y - 1, 4);
+        assert_eq!(Difficulty::detect(&map), Difficulty::Kind);
+    }
+
+    #[test]
+    fn test_check() {
+        assert!(Difficulty::Mean.check(&get_map(4, 242)).is_ok());
+        assert!(Difficulty::Kind.check(&get_map(4, 242)).is_err());
+        assert!(Difficulty::Mean.check(&get_map(3, 242)).is_err());
+        // three colors left in a played game
+        assert!(Difficulty::Mean.check(&get_map(3, 60)).is_ok());
+        assert!(Difficulty::Gentle.check(&get_map(2, 200)).is_ok());
+        assert_eq!("Mean".parse::<Difficulty>(), Ok(Difficulty::Mean));
+        assert!("hard".parse::<Difficulty>().is_err());
+    }
+}
@@ -1,8 +1,184 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::difficulty::Difficulty;
+
 #[derive(Debug)]
 pub enum MyError {
     IO(std::io::Error),
     JSON(serde_json::error::Error),
     Image(image::ImageError),
+    /// Error while reading or writing the file.
+    File {
+        path: PathBuf,
+        source: Box<MyError>,
+    },
+    InvalidTarget(String),
+    InvalidGeometry(String),
+    /// Screenshot size (width, height) which can't be matched to the game layout.
+    UnsupportedAspectRatio(u32, u32),
+    GridNotFound,
+    Calibration(String),
+    InvalidInput(String),
+    /// Tiles (x, y) which couldn't be recognised reliably.
+    AmbiguousTiles(Vec<(usize, usize)>),
+    /// Board which doesn't look like the selected difficulty.
+    DifficultyMismatch {
+        expected: Difficulty,
+        found: Difficulty,
+    },
+    /// Board with a different number of rows than the game.
+    InvalidMapSize(usize),
+    /// Row of the board with a different number of columns than the game.
+    InvalidRowLength {
+        row: usize,
+        columns: usize,
+    },
+    /// Cell (x, y) with a value which is no tile type.
+    InvalidTile {
+        x: usize,
+        y: usize,
+        value: String,
+    },
+    /// `.map` file written with a newer schema version.
+    UnsupportedMapVersion(u32),
+    /// `.map` file for a board of another size.
+    InvalidMapDimensions {
+        columns: usize,
+        rows: usize,
+    },
+    /// `.map` file with guessed tiles which hasn't been checked by hand yet.
+    UnverifiedMap,
+    /// The solving method or the `target` objective needs a target pattern, see `--target`.
+    MissingTarget,
+    /// A separate part of the board (tiles, groups) has too many groups for the
+    /// solving method.
+    ClusterTooLarge {
+        tiles: usize,
+        groups: usize,
+    },
+    /// Job queue of a solver is full.
+    QueueOverflow,
+    /// Thread of a solver panicked.
+    WorkerPanicked,
+}
+
+impl fmt::Display for MyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MyError::IO(_) => write!(f, "input/output error"),
+            MyError::JSON(_) => write!(f, "invalid JSON"),
+            MyError::Image(_) => write!(f, "can't read the image"),
+            MyError::File { path, .. } => write!(f, "file {:?}", path),
+            MyError::InvalidTarget(message) => write!(f, "invalid target: {}", message),
+            MyError::InvalidGeometry(message) => write!(f, "invalid grid geometry: {}", message),
+            MyError::UnsupportedAspectRatio(width, height) => write!(
+                f,
+                "screenshot size {}x{} doesn't match the game layout",
+                width, height
+            ),
+            MyError::GridNotFound => write!(f, "no tile grid found on the screenshot"),
+            MyError::Calibration(message) => write!(f, "calibration failed: {}", message),
+            MyError::InvalidInput(message) => write!(f, "invalid input: {}", message),
+            MyError::AmbiguousTiles(tiles) => {
+                write!(f, "tiles {:?} can't be recognised reliably", tiles)
+            }
+            MyError::DifficultyMismatch { expected, found } => write!(
+                f,
+                "the board looks like {:?} difficulty instead of {:?}",
+                found, expected
+            ),
+            MyError::InvalidMapSize(rows) => write!(
+                f,
+                "the board has {} rows instead of {}",
+                rows,
+                crate::map::MAX_Y
+            ),
+            MyError::InvalidRowLength { row, columns } => write!(
+                f,
+                "row {} has {} columns instead of {}",
+                row,
+                columns,
+                crate::map::MAX_X
+            ),
+            MyError::InvalidTile { x, y, value } => write!(
+                f,
+                "row {}, column {} has the value {}, tiles are 0 (none) to 4",
+                y, x, value
+            ),
+            MyError::UnsupportedMapVersion(version) => write!(
+                f,
+                "the map file has version {}, this program reads up to version {}",
+                version,
+                crate::map_file::MAP_FILE_VERSION
+            ),
+            MyError::InvalidMapDimensions { columns, rows } => write!(
+                f,
+                "the map file is for a {}x{} board instead of {}x{}",
+                columns,
+                rows,
+                crate::map::MAX_X,
+                crate::map::MAX_Y
+            ),
+            MyError::UnverifiedMap => write!(
+                f,
+                "the board has tiles which couldn't be recognised reliably, fix them or \
+                 remove the `unverified` entry if they are right"
+            ),
+            MyError::MissingTarget => write!(
+                f,
+                "the solving method or the `target` objective requires a target pattern"
+            ),
+            MyError::ClusterTooLarge { tiles, groups } => write!(
+                f,
+                "a part of the board with {} tiles has too many groups ({}) to solve",
+                tiles, groups
+            ),
+            MyError::QueueOverflow => write!(f, "the job queue of the solver is full"),
+            MyError::WorkerPanicked => write!(f, "a thread of the solver panicked"),
+        }
+    }
+}
+
+impl std::error::Error for MyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MyError::IO(error) => Some(error),
+            MyError::JSON(error) => Some(error),
+            MyError::Image(error) => Some(error),
+            MyError::File { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl MyError {
+    /// The error with all its sources, like `file "a.map": invalid JSON: EOF while
+    /// parsing a value at line 1 column 0`.
+    pub fn report(&self) -> String {
+        let mut message = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(error) = source {
+            message.push_str(": ");
+            message.push_str(&error.to_string());
+            source = error.source();
+        }
+        message
+    }
+}
+
+/// Adds the path of the file to errors.
+pub trait WithPath<T> {
+    fn with_path(self, path: &Path) -> Result<T, MyError>;
+}
+
+impl<T, E: Into<MyError>> WithPath<T> for Result<T, E> {
+    fn with_path(self, path: &Path) -> Result<T, MyError> {
+        self.map_err(|error| MyError::File {
+            path: path.to_path_buf(),
+            source: Box::new(error.into()),
+        })
+    }
 }
 
 impl From<std::io::Error> for MyError {
@@ -22,3 +198,26 @@ impl From<image::ImageError> for MyError {
         MyError::Image(error)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{MyError, WithPath};
+    use std::path::Path;
+
+    #[test]
+    fn test_report() {
+        let error = std::fs::read("missing/board.map")
+            .with_path(Path::new("missing/board.map"))
+            .unwrap_err();
+        assert!(
+            matches!(&error, MyError::File { source, .. } if matches!(**source, MyError::IO(_)))
+        );
+        assert!(error
+            .report()
+            .starts_with("file \"missing/board.map\": input/output error: "));
+        assert_eq!(
+            MyError::MissingTarget.report(),
+            MyError::MissingTarget.to_string()
+        );
+    }
+}
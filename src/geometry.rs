@@ -0,0 +1,287 @@
+use image::{DynamicImage, GenericImageView};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+
+use crate::detect::detect_grid;
+use crate::errors::{MyError, WithPath};
+
+const REFERENCE_WIDTH: f64 = 1920.0;
+const REFERENCE_HEIGHT: f64 = 1080.0;
+/// Narrowest supported screen (16:10), the game adds black bars above and below the picture.
+const MIN_ASPECT_RATIO: f64 = 16.0 / 10.0;
+/// Widest supported screen (32:9), the game adds black bars on the sides of the picture.
+const MAX_ASPECT_RATIO: f64 = 32.0 / 9.0;
+
+/// Built-in profiles, the game renders the board proportionally to the screen height.
+pub const PROFILES: [(u32, u32); 5] = [
+    (1280, 720),
+    (1600, 900),
+    (1920, 1080),
+    (2560, 1440),
+    (3840, 2160),
+];
+
+/// Position of the tile grid on a screenshot, in pixels.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GridGeometry {
+    /// Left edge of the first column.
+    pub origin_x: f64,
+    /// Top edge of the first row.
+    pub origin_y: f64,
+    /// Distance between neighbouring tiles.
+    pub tile_size: f64,
+    /// Pixels skipped on every side of a tile when sampling its color.
+    pub border_width: f64,
+    pub columns: u32,
+    pub rows: u32,
+}
+
+/// Layout of a 1920x1080 screenshot.
+impl Default for GridGeometry {
+    fn default() -> Self {
+        GridGeometry {
+            origin_x: 80.0,
+            origin_y: 80.0,
+            tile_size: 80.0,
+            border_width: 4.0,
+            columns: 22,
+            rows: 11,
+        }
+    }
+}
+
+impl GridGeometry {
+    pub fn load(path: &Path) -> Result<GridGeometry, MyError> {
+        let mut file = std::fs::File::open(path).with_path(path)?;
+        let mut buffer = String::new();
+        file.read_to_string(&mut buffer).with_path(path)?;
+        serde_json::from_str::<GridGeometry>(&buffer).with_path(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), MyError> {
+        let file = std::fs::File::create(path).with_path(path)?;
+        serde_json::to_writer_pretty(file, self).with_path(path)
+    }
+
+    /// Built-in profile by its name, e.g. `2560x1440`.
+    pub fn from_profile(name: &str) -> Option<GridGeometry> {
+        PROFILES
+            .iter()
+            .find(|x| format!("{}x{}", x.0, x.1) == name)
+            .map(|x| GridGeometry::default().scaled(f64::from(x.1) / REFERENCE_HEIGHT))
+    }
+
+    /// Loads a profile file, or a built-in profile if `value` is a profile name.
+    /// Use `GeometrySource` to also accept `auto`.
+    pub fn from_profile_or_file(value: &str) -> Result<GridGeometry, MyError> {
+        match GridGeometry::from_profile(value) {
+            Some(geometry) => Ok(geometry),
+            None => GridGeometry::load(Path::new(value)),
+        }
+    }
+
+    /// Scales the 1920x1080 layout to a screenshot of the given size. Screens narrower
+    /// than 16:9 are letterboxed and wider ones are pillarboxed, the picture stays centered.
+    pub fn for_image(width: u32, height: u32) -> Result<GridGeometry, MyError> {
+        if width == 0 || height == 0 {
+            return Err(MyError::UnsupportedAspectRatio(width, height));
+        }
+        let (width, height) = (f64::from(width), f64::from(height));
+        let aspect_ratio = width / height;
+        // small tolerance for odd sizes like 1366x768
+        if !(MIN_ASPECT_RATIO * 0.99..=MAX_ASPECT_RATIO * 1.01).contains(&aspect_ratio) {
+            return Err(MyError::UnsupportedAspectRatio(width as u32, height as u32));
+        }
+
+        let factor = (width / REFERENCE_WIDTH).min(height / REFERENCE_HEIGHT);
+        let offset_x = (width - REFERENCE_WIDTH * factor) / 2.0;
+        let offset_y = (height - REFERENCE_HEIGHT * factor) / 2.0;
+        let geometry = GridGeometry::default().scaled(factor);
+        Ok(GridGeometry {
+            origin_x: geometry.origin_x + offset_x,
+            origin_y: geometry.origin_y + offset_y,
+            ..geometry
+        })
+    }
+
+    pub fn scaled(&self, factor: f64) -> GridGeometry {
+        GridGeometry {
+            origin_x: self.origin_x * factor,
+            origin_y: self.origin_y * factor,
+            tile_size: self.tile_size * factor,
+            border_width: self.border_width * factor,
+            ..self.clone()
+        }
+    }
+
+    /// Sampled area of the tile as `(x, y, width, height)`, without the border.
+    pub fn get_tile_rect(&self, tile_x: u32, tile_y: u32) -> (u32, u32, u32, u32) {
+        let start_x = self.origin_x + f64::from(tile_x) * self.tile_size + self.border_width;
+        let start_y = self.origin_y + f64::from(tile_y) * self.tile_size + self.border_width;
+        let size = (self.tile_size - self.border_width * 2.0).max(1.0);
+        (
+            start_x.round() as u32,
+            start_y.round() as u32,
+            size.round() as u32,
+            size.round() as u32,
+        )
+    }
+
+    /// Checks that the grid is on the board and fits into an image of the given size.
+    pub fn validate(&self, image_width: u32, image_height: u32) -> Result<(), MyError> {
+        if self.origin_x < 0.0 || self.origin_y < 0.0 || self.border_width < 0.0 {
+            return Err(MyError::InvalidGeometry(
+                "grid origin and border can't be negative".to_string(),
+            ));
+        }
+        if self.tile_size <= self.border_width * 2.0 {
+            return Err(MyError::InvalidGeometry(
+                "tiles are smaller than their borders".to_string(),
+            ));
+        }
+        if self.columns == 0 || self.rows == 0 {
+            return Err(MyError::InvalidGeometry("grid is empty".to_string()));
+        }
+        let right = self.origin_x + f64::from(self.columns) * self.tile_size;
+        let bottom = self.origin_y + f64::from(self.rows) * self.tile_size;
+        if right > f64::from(image_width) || bottom > f64::from(image_height) {
+            return Err(MyError::InvalidGeometry(format!(
+                "grid ends at {:.0}x{:.0}, outside of the {}x{} image",
+                right, bottom, image_width, image_height
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Where the grid geometry of a screenshot comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GeometrySource {
+    /// Scaled from the screenshot size, see `GridGeometry::for_image`.
+    Auto,
+    /// Found on the screenshot, see `detect_grid`.
+    Detect,
+    Fixed(GridGeometry),
+}
+
+impl GeometrySource {
+    /// `auto`, `detect`, a built-in profile name or a path to a profile file.
+    pub fn parse(value: &str) -> Result<GeometrySource, MyError> {
+        if value == "auto" {
+            Ok(GeometrySource::Auto)
+        } else if value == "detect" {
+            Ok(GeometrySource::Detect)
+        } else {
+            Ok(GeometrySource::Fixed(GridGeometry::from_profile_or_file(
+                value,
+            )?))
+        }
+    }
+
+    pub fn resolve(&self, image: &DynamicImage) -> Result<GridGeometry, MyError> {
+        match self {
+            GeometrySource::Detect => detect_grid(image),
+            _ => self.resolve_for_size(image.width(), image.height()),
+        }
+    }
+
+    /// Geometry for a screenshot of this size, without looking at the screenshot, so
+    /// `Detect` can't be resolved.
+    pub fn resolve_for_size(&self, width: u32, height: u32) -> Result<GridGeometry, MyError> {
+        match self {
+            GeometrySource::Auto => GridGeometry::for_image(width, height),
+            GeometrySource::Detect => Err(MyError::InvalidGeometry(
+                "`detect` needs a screenshot to find the grid on".to_string(),
+            )),
+            GeometrySource::Fixed(geometry) => Ok(geometry.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DynamicImage, GeometrySource, GridGeometry};
+
+    #[test]
+    fn test_profiles() {
+        assert_eq!(
+            GridGeometry::from_profile("1920x1080"),
+            Some(GridGeometry::default())
+        );
+        let geometry = GridGeometry::from_profile("3840x2160").unwrap();
+        assert_eq!(geometry.tile_size, 160.0);
+        assert_eq!(geometry.border_width, 8.0);
+        assert_eq!(geometry.columns, 22);
+
+        let geometry = GridGeometry::from_profile("1280x720").unwrap();
+        assert!((geometry.tile_size - 53.333).abs() < 1e-3);
+        assert!(geometry.validate(1280, 720).is_ok());
+        assert!(GridGeometry::from_profile("1024x768").is_none());
+    }
+
+    #[test]
+    fn test_tile_rect() {
+        let geometry = GridGeometry::default();
+        assert_eq!(geometry.get_tile_rect(0, 0), (84, 84, 72, 72));
+        assert_eq!(geometry.get_tile_rect(21, 10), (1764, 884, 72, 72));
+        assert!(geometry.validate(1920, 1080).is_ok());
+        assert!(geometry.validate(1280, 720).is_err());
+
+        let broken = GridGeometry {
+            border_width: 40.0,
+            ..GridGeometry::default()
+        };
+        assert!(broken.validate(1920, 1080).is_err());
+    }
+
+    #[test]
+    fn test_scaling_from_image_size() {
+        for profile in &super::PROFILES {
+            let name = format!("{}x{}", profile.0, profile.1);
+            assert_eq!(
+                GridGeometry::for_image(profile.0, profile.1).unwrap(),
+                GridGeometry::from_profile(&name).unwrap()
+            );
+        }
+
+        // 16:10, black bars of 60 px above and below
+        let letterbox = GridGeometry::for_image(1920, 1200).unwrap();
+        assert_eq!(letterbox.origin_x, 80.0);
+        assert_eq!(letterbox.origin_y, 140.0);
+        assert_eq!(letterbox.tile_size, 80.0);
+        assert!(letterbox.validate(1920, 1200).is_ok());
+
+        // 21:9, black bars of 440 px on the sides
+        let pillarbox = GridGeometry::for_image(3440, 1440).unwrap();
+        assert!((pillarbox.tile_size - 106.667).abs() < 1e-3);
+        assert!((pillarbox.origin_x - (440.0 + 106.667)).abs() < 1e-3);
+        assert!((pillarbox.origin_y - 106.667).abs() < 1e-3);
+
+        assert!(GridGeometry::for_image(1024, 768).is_err());
+        assert!(GridGeometry::for_image(1080, 1920).is_err());
+        assert!(GridGeometry::for_image(0, 0).is_err());
+    }
+
+    #[test]
+    fn test_geometry_source() {
+        assert_eq!(GeometrySource::parse("auto").unwrap(), GeometrySource::Auto);
+        assert_eq!(
+            GeometrySource::parse("detect").unwrap(),
+            GeometrySource::Detect
+        );
+        let image = DynamicImage::new_rgb8(2560, 1440);
+        let fixed = GeometrySource::parse("1920x1080").unwrap();
+        assert_eq!(fixed.resolve(&image).unwrap(), GridGeometry::default());
+        assert_eq!(
+            GeometrySource::Auto.resolve(&image).unwrap(),
+            GridGeometry::from_profile("2560x1440").unwrap()
+        );
+        assert_eq!(
+            GeometrySource::Auto.resolve_for_size(2560, 1440).unwrap(),
+            GridGeometry::from_profile("2560x1440").unwrap()
+        );
+        assert!(GeometrySource::Detect.resolve_for_size(2560, 1440).is_err());
+        assert!(GeometrySource::parse("missing_profile.json").is_err());
+    }
+}
@@ -0,0 +1,277 @@
+use image::{DynamicImage, GenericImageView};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::errors::{MyError, WithPath};
+use crate::map_file::hash_source;
+
+/// Extensions of the screenshots picked up from a directory.
+pub const IMAGE_EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "bmp", "tga"];
+/// Command line argument for reading a screenshot from stdin.
+pub const STDIN_ARGUMENT: &str = "-";
+/// Files for a screenshot from stdin (`.map`, `.ahk`) are named after this and a hash
+/// of the screenshot, so a different screenshot doesn't reuse the files of the last one.
+const STDIN_NAME: &str = "stdin";
+
+/// Screenshot to recognise.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageInput {
+    File(PathBuf),
+    /// Image read into memory, the files for it are written next to `path`.
+    Memory {
+        path: PathBuf,
+        data: Vec<u8>,
+    },
+}
+
+impl ImageInput {
+    /// Screenshot read from stdin.
+    pub fn from_stdin_data(data: Vec<u8>) -> ImageInput {
+        ImageInput::Memory {
+            path: PathBuf::from(format!("{}-{}", STDIN_NAME, hash_source(&data))),
+            data,
+        }
+    }
+
+    /// `.map` file given instead of a screenshot, like the boards found by `video`.
+    pub fn is_map(&self) -> bool {
+        match self {
+            ImageInput::File(path) => path.extension() == Some(std::ffi::OsStr::new("map")),
+            ImageInput::Memory { .. } => false,
+        }
+    }
+
+    /// Path which the `.map` and `.ahk` files of the screenshot are named after.
+    pub fn path(&self) -> &Path {
+        match self {
+            ImageInput::File(path) => path,
+            ImageInput::Memory { path, .. } => path,
+        }
+    }
+
+    pub fn read(&self) -> Result<Vec<u8>, MyError> {
+        match self {
+            ImageInput::File(path) => std::fs::read(path).with_path(path),
+            ImageInput::Memory { data, .. } => Ok(data.clone()),
+        }
+    }
+
+    pub fn decode(&self) -> Result<DynamicImage, MyError> {
+        image::load_from_memory(&self.read()?).with_path(self.path())
+    }
+
+    /// Size of the image, files are only decoded as far as needed.
+    pub fn dimensions(&self) -> Result<(u32, u32), MyError> {
+        match self {
+            ImageInput::File(path) => image::image_dimensions(path).with_path(path),
+            ImageInput::Memory { .. } => {
+                let image = self.decode()?;
+                Ok((image.width(), image.height()))
+            }
+        }
+    }
+}
+
+pub fn is_image(path: &Path) -> bool {
+    match path.extension() {
+        Some(extension) => {
+            let extension = extension.to_string_lossy().to_lowercase();
+            IMAGE_EXTENSIONS.iter().any(|x| *x == extension)
+        }
+        None => false,
+    }
+}
+
+/// All screenshots in the directory, sorted by name.
+pub fn find_images(directory: &Path) -> Result<Vec<PathBuf>, MyError> {
+    let mut results = vec![];
+    for entry in directory.read_dir()? {
+        let path = entry?.path();
+        if path.is_file() && is_image(&path) {
+            results.push(path);
+        }
+    }
+    results.sort();
+    Ok(results)
+}
+
+/// Matches the name against a pattern with `*` (any number of characters) and `?`
+/// (exactly one character).
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    // position in the pattern after the last `*` and the name position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p + 1, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = backtrack {
+            // let the last `*` take one more character
+            p = star_p;
+            n = star_n + 1;
+            backtrack = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|x| *x == '*')
+}
+
+fn has_wildcards(value: &str) -> bool {
+    value.contains('*') || value.contains('?')
+}
+
+/// Files matching a pattern with wildcards in the file name, like `screens/*.jpg`.
+/// Only screenshots are matched, unless the pattern names the extension without
+/// wildcards, like `game/*.map`.
+pub fn expand_pattern(pattern: &str) -> Result<Vec<PathBuf>, MyError> {
+    let path = Path::new(pattern);
+    let name = path
+        .file_name()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let directory = match path.parent() {
+        Some(parent) if parent != Path::new("") => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    if has_wildcards(&directory.to_string_lossy()) {
+        return Err(MyError::InvalidInput(format!(
+            "wildcards are only supported in file names: '{}'",
+            pattern
+        )));
+    }
+    let images_only = match Path::new(&name).extension() {
+        Some(extension) => has_wildcards(&extension.to_string_lossy()),
+        None => true,
+    };
+
+    let mut results = vec![];
+    for entry in directory.read_dir()? {
+        let entry_path = entry?.path();
+        let entry_name = entry_path
+            .file_name()
+            .map(|x| x.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if entry_path.is_file()
+            && matches_pattern(&name, &entry_name)
+            && (!images_only || is_image(&entry_path))
+        {
+            results.push(entry_path);
+        }
+    }
+    results.sort();
+    Ok(results)
+}
+
+/// Screenshots from the command line arguments: files, patterns and `-` for stdin,
+/// which is only read once. Without arguments all screenshots in the current directory
+/// are used.
+pub fn get_inputs(arguments: &[String]) -> Result<Vec<ImageInput>, MyError> {
+    if arguments.is_empty() {
+        let files = find_images(&std::env::current_dir()?)?;
+        return Ok(files.into_iter().map(ImageInput::File).collect());
+    }
+
+    let mut inputs = vec![];
+    let mut stdin_read = false;
+    for argument in arguments {
+        if argument == STDIN_ARGUMENT {
+            if stdin_read {
+                continue;
+            }
+            stdin_read = true;
+            let mut data = vec![];
+            std::io::stdin().read_to_end(&mut data)?;
+            inputs.push(ImageInput::from_stdin_data(data));
+        } else if has_wildcards(argument) {
+            let files = expand_pattern(argument)?;
+            if files.is_empty() {
+                return Err(MyError::InvalidInput(format!(
+                    "no files match '{}'",
+                    argument
+                )));
+            }
+            inputs.extend(files.into_iter().map(ImageInput::File));
+        } else {
+            inputs.push(ImageInput::File(PathBuf::from(argument)));
+        }
+    }
+    Ok(inputs)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{expand_pattern, find_images, is_image, matches_pattern, ImageInput};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("*.png", "screen.png"));
+        assert!(matches_pattern("*.png", ".png"));
+        assert!(!matches_pattern("*.png", "screen.jpg"));
+        assert!(matches_pattern("screen?.jpg", "screen1.jpg"));
+        assert!(!matches_pattern("screen?.jpg", "screen.jpg"));
+        assert!(matches_pattern("*board*", "my_board_2.bmp"));
+        assert!(matches_pattern("a*b*c", "aXbYbZc"));
+        assert!(!matches_pattern("a*b*c", "aXbYbZ"));
+        assert!(matches_pattern("*", ""));
+        assert!(matches_pattern("exact.tga", "exact.tga"));
+    }
+
+    #[test]
+    fn test_stdin_names() {
+        let first = ImageInput::from_stdin_data(b"first".to_vec());
+        let second = ImageInput::from_stdin_data(b"second".to_vec());
+        assert!(first.path().to_string_lossy().starts_with("stdin-"));
+        assert_ne!(first.path(), second.path());
+        assert_eq!(
+            first.path(),
+            ImageInput::from_stdin_data(b"first".to_vec()).path()
+        );
+    }
+
+    #[test]
+    fn test_is_image() {
+        assert!(is_image(Path::new("a.png")));
+        assert!(is_image(Path::new("dir/a.JPG")));
+        assert!(is_image(Path::new("a.tga")));
+        // the WebP decoder of `image` 0.22 only reads lossy images, as grayscale
+        assert!(!is_image(Path::new("a.webp")));
+        assert!(!is_image(Path::new("a.map")));
+        assert!(!is_image(Path::new("png")));
+        assert!(ImageInput::File(PathBuf::from("board.map")).is_map());
+        assert!(!ImageInput::File(PathBuf::from("board.png")).is_map());
+    }
+
+    #[test]
+    fn test_find_images() {
+        let directory = std::env::temp_dir().join(format!(
+            "monolith_solver_test_find_images_{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&directory).unwrap();
+        for name in &["b.jpg", "a.png", "a.map", "c.ahk", "d.Bmp"] {
+            std::fs::write(directory.join(name), b"").unwrap();
+        }
+
+        let found = find_images(&directory);
+        let expanded = expand_pattern(&directory.join("?.*p*").to_string_lossy());
+        let maps = expand_pattern(&directory.join("*.map").to_string_lossy());
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let names = |paths: Vec<std::path::PathBuf>| {
+            paths
+                .iter()
+                .map(|x| x.file_name().unwrap().to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(found.unwrap()), vec!["a.png", "b.jpg", "d.Bmp"]);
+        assert_eq!(names(expanded.unwrap()), vec!["a.png", "b.jpg", "d.Bmp"]);
+        assert_eq!(names(maps.unwrap()), vec!["a.map"]);
+    }
+}
@@ -1,3 +1,17 @@
+pub mod calibration;
+pub mod cluster;
 pub mod create_ahk;
+pub mod detect;
+pub mod difficulty;
 pub mod errors;
+pub mod geometry;
+pub mod input;
 pub mod map;
+pub mod map_file;
+pub mod overlay;
+pub mod recognize;
+pub mod render;
+pub mod sample;
+pub mod solution;
+pub mod tune;
+pub mod video;
@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+
+use super::MonolithMap;
+use crate::errors::{MyError, WithPath};
+
+/// Weights of the board evaluation used by heuristic solvers.
+/// Default values reproduce the original "average group size" score.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct HeuristicWeights {
+    pub group_count: f64,
+    pub groups_total_size: f64,
+    pub average_group_size: f64,
+    pub dead_tiles: f64,
+}
+
+impl Default for HeuristicWeights {
+    fn default() -> Self {
+        HeuristicWeights {
+            group_count: 0.0,
+            groups_total_size: 0.0,
+            average_group_size: 1.0,
+            dead_tiles: 0.0,
+        }
+    }
+}
+
+impl HeuristicWeights {
+    pub const LEN: usize = 4;
+
+    pub fn load(path: &Path) -> Result<HeuristicWeights, MyError> {
+        let mut file = std::fs::File::open(path).with_path(path)?;
+        let mut buffer = String::new();
+        file.read_to_string(&mut buffer).with_path(path)?;
+        serde_json::from_str::<HeuristicWeights>(&buffer).with_path(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), MyError> {
+        let file = std::fs::File::create(path).with_path(path)?;
+        serde_json::to_writer_pretty(file, self).with_path(path)
+    }
+
+    pub fn to_vec(&self) -> Vec<f64> {
+        vec![
+            self.group_count,
+            self.groups_total_size,
+            self.average_group_size,
+            self.dead_tiles,
+        ]
+    }
+
+    pub fn from_slice(values: &[f64]) -> HeuristicWeights {
+        debug_assert_eq!(values.len(), Self::LEN);
+        HeuristicWeights {
+            group_count: values[0],
+            groups_total_size: values[1],
+            average_group_size: values[2],
+            dead_tiles: values[3],
+        }
+    }
+
+    /// Higher is better.
+    pub fn evaluate(&self, map: &MonolithMap) -> f64 {
+        let groups = map.all_groups();
+        let group_count = groups.len() as f64;
+        let groups_total_size = groups.iter().fold(0, |sum, e| sum + e.len()) as f64;
+        let average_group_size = if groups.is_empty() {
+            0.0
+        } else {
+            groups_total_size / group_count
+        };
+
+        self.group_count * group_count
+            + self.groups_total_size * groups_total_size
+            + self.average_group_size * average_group_size
+            + self.dead_tiles * f64::from(map.get_dead_tiles_count())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{HeuristicWeights, MonolithMap};
+
+    #[test]
+    fn test_default_weights_use_average_group_size() {
+        let map = "
+            32..................21
+            1....................3
+            ......................
+            ......................
+            ......................
+            ......................
+            ...........2..........
+            ..........222.........
+            .3.........2..........
+            214..................1
+            .1..................11
+        "
+        .parse::<MonolithMap>()
+        .unwrap();
+
+        // groups of 5, 2 and 3 tiles
+        let score = HeuristicWeights::default().evaluate(&map);
+        assert!((score - 10.0 / 3.0).abs() < 1e-9);
+
+        let weights = HeuristicWeights::from_slice(&[1.0, 0.5, 0.0, -1.0]);
+        assert_eq!(weights.to_vec(), vec![1.0, 0.5, 0.0, -1.0]);
+        let dead = f64::from(map.get_dead_tiles_count());
+        assert!((weights.evaluate(&map) - (3.0 + 5.0 - dead)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_empty_map_evaluates_to_zero() {
+        let score = HeuristicWeights::default().evaluate(&MonolithMap::default());
+        assert_eq!(score, 0.0);
+    }
+}
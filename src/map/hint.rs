@@ -0,0 +1,192 @@
+use std::time::Duration;
+
+use super::methods::{SolverOptions, SolvingMethods};
+use super::monolith_map::{MonolithMap, Tile};
+use crate::errors::MyError;
+
+/// Candidate click for the current board.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hint {
+    /// Tile to click.
+    pub tile: Tile,
+    /// Size of the group which the click removes.
+    pub group_size: usize,
+    /// Tiles left on the board after the best continuation found for this click.
+    pub remaining_tiles: u32,
+    /// Share of the confidence among all candidates, a softmax over the value of the most
+    /// important objective (see `Ranking::get_primary_cost`).
+    pub confidence: f64,
+    /// Best continuation found after this click.
+    pub steps: Vec<Tile>,
+}
+
+/// Returns up to `count` best clicks for `map`, best first.
+/// Every group is tried once and the board after the click is solved with `method`,
+/// `time_budget` is split evenly between the candidates.
+pub fn get_hints(
+    map: &MonolithMap,
+    method: &SolvingMethods,
+    options: &SolverOptions,
+    count: usize,
+    time_budget: Duration,
+) -> Result<Vec<Hint>, MyError> {
+    let groups = map.all_groups();
+    if groups.is_empty() || count == 0 {
+        return Ok(Vec::new());
+    }
+
+    let candidate_options = SolverOptions {
+        time_limit: time_budget / groups.len() as u32,
+        ..options.clone()
+    };
+    let ranking = options.ranking(map);
+    let mut candidates = groups
+        .iter()
+        .map(|group| {
+            let tile = group[0];
+            let mut new_map = map.clone();
+            new_map.click(tile.0, tile.1);
+            let steps = method
+                .clone()
+                .solve_with(new_map.clone(), &candidate_options)?;
+            for step in &steps {
+                new_map.click(step.0, step.1);
+            }
+
+            let mut path = Vec::with_capacity(steps.len() + 1);
+            path.push(tile);
+            path.extend(steps.iter().cloned());
+            let hint = Hint {
+                tile,
+                group_size: group.len(),
+                remaining_tiles: new_map.get_all_tiles_count(),
+                confidence: 0.0,
+                steps,
+            };
+            Ok((ranking.path(hint.remaining_tiles, path), hint))
+        })
+        .collect::<Result<Vec<_>, MyError>>()?;
+    candidates.sort_by(|a, b| ranking.compare(&a.0, &b.0));
+
+    let costs = candidates
+        .iter()
+        .map(|x| ranking.get_primary_cost(&x.0))
+        .collect::<Vec<_>>();
+    let best_cost = costs[0];
+    let total = costs.iter().map(|&x| get_weight(x, best_cost)).sum::<f64>();
+    Ok(candidates
+        .into_iter()
+        .zip(costs)
+        .take(count)
+        .map(|((_, mut hint), cost)| {
+            hint.confidence = get_weight(cost, best_cost) / total;
+            hint
+        })
+        .collect())
+}
+
+/// Softmax weight, shifted by the smallest cost to keep it finite.
+fn get_weight(cost: f64, best_cost: f64) -> f64 {
+    (best_cost.min(cost) - cost).exp()
+}
+
+#[cfg(test)]
+mod test {
+    use super::get_hints;
+    use crate::map::{MonolithMap, SolverOptions, SolvingMethods};
+    use std::time::Duration;
+
+    #[test]
+    fn test_hints() {
+        let map = "
+            ......................
+            ......................
+            ......................
+            ......................
+            ......................
+            ......................
+            ......................
+            ......332.............
+            ........11.......242..
+            ..3.....32............
+            ..4...................
+        "
+        .parse::<MonolithMap>()
+        .unwrap();
+        let options = SolverOptions::default();
+        let method = SolvingMethods::Method4;
+        let budget = Duration::from_secs(1);
+        let hints = get_hints(&map, &method, &options, 5, budget).unwrap();
+
+        assert_eq!(hints.len(), map.all_groups().len());
+        for pair in hints.windows(2) {
+            assert!(pair[0].remaining_tiles <= pair[1].remaining_tiles);
+            assert!(pair[0].confidence >= pair[1].confidence);
+        }
+        let total = hints.iter().map(|x| x.confidence).sum::<f64>();
+        assert!((total - 1.0).abs() < 1e-9);
+
+        let best = &hints[0];
+        let mut played = map.clone();
+        played.click(best.tile.0, best.tile.1);
+        for step in &best.steps {
+            played.click(step.0, step.1);
+        }
+        assert_eq!(played.get_all_tiles_count(), best.remaining_tiles);
+
+        // the best hint is as good as solving the whole board
+        let mut solved = map.clone();
+        for step in map.clone().solve(method.clone()).unwrap() {
+            solved.click(step.0, step.1);
+        }
+        assert_eq!(solved.get_all_tiles_count(), best.remaining_tiles);
+
+        let top = get_hints(&map, &method, &options, 1, budget).unwrap();
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].tile, best.tile);
+        assert_eq!(top[0].remaining_tiles, best.remaining_tiles);
+        assert_eq!(top[0].confidence, best.confidence);
+        assert!(
+            get_hints(&MonolithMap::default(), &method, &options, 3, budget)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_hints_follow_first_objective() {
+        let map = "
+            ......................
+            ......................
+            ......................
+            ......................
+            ......................
+            ......................
+            ......................
+            ......332.............
+            ........11.......242..
+            ..3.....32............
+            ..4...................
+        "
+        .parse::<MonolithMap>()
+        .unwrap();
+        let options = SolverOptions {
+            objectives: "clicks,tiles".parse().unwrap(),
+            ..SolverOptions::default()
+        };
+        let method = SolvingMethods::Method4;
+        let hints = get_hints(&map, &method, &options, 5, Duration::from_secs(1)).unwrap();
+
+        // the confidence follows the clicks, whatever the tiles left
+        for pair in hints.windows(2) {
+            assert!(pair[0].steps.len() <= pair[1].steps.len());
+            if pair[0].steps.len() == pair[1].steps.len() {
+                assert!((pair[0].confidence - pair[1].confidence).abs() < 1e-9);
+            } else {
+                assert!(pair[0].confidence > pair[1].confidence);
+            }
+        }
+        let total = hints.iter().map(|x| x.confidence).sum::<f64>();
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+}
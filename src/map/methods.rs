@@ -1,8 +1,14 @@
+use serde::{Deserialize, Serialize};
 use std::str::FromStr;
+use std::time::Duration;
 
-use super::{monolith_solver, MonolithMap, Tile};
+use super::objective::{Objective, Objectives, Ranking};
+use super::score::ScoreRules;
+use super::target::TargetSpec;
+use super::{monolith_solver, HeuristicWeights, MonolithMap, Tile};
+use crate::errors::MyError;
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum SolvingMethods {
     Method1,
     Method2,
@@ -17,24 +23,72 @@ pub enum SolvingMethods {
     Method11,
 }
 
-impl SolvingMethods {
-    pub fn default() -> SolvingMethods {
+impl Default for SolvingMethods {
+    fn default() -> Self {
         SolvingMethods::Method4
     }
+}
 
-    pub fn solve(self, map: MonolithMap) -> Vec<Tile> {
+/// Settings shared by all solving methods.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SolverOptions {
+    pub weights: HeuristicWeights,
+    pub objectives: Objectives,
+    pub score_rules: ScoreRules,
+    /// Board pattern for `Objective::Target` and `Method9`.
+    pub target: Option<TargetSpec>,
+    /// How long the search of a method may take, then it returns the best path found.
+    pub time_limit: Duration,
+}
+
+impl Default for SolverOptions {
+    fn default() -> Self {
+        SolverOptions {
+            weights: HeuristicWeights::default(),
+            objectives: Objectives::default(),
+            score_rules: ScoreRules::default(),
+            target: None,
+            time_limit: Duration::from_secs(60),
+        }
+    }
+}
+
+impl SolverOptions {
+    pub fn ranking(&self, map: &MonolithMap) -> Ranking {
+        Ranking::new(&self.objectives, map, &self.score_rules).with_target(self.target.as_ref())
+    }
+}
+
+impl SolvingMethods {
+    pub fn solve(self, map: MonolithMap) -> Result<Vec<Tile>, MyError> {
+        self.solve_with(map, &SolverOptions::default())
+    }
+
+    /// Methods which evaluate boards with `HeuristicWeights` and can be tuned.
+    pub fn uses_weights(&self) -> bool {
+        matches!(self, SolvingMethods::Method10)
+    }
+
+    pub fn solve_with(
+        self,
+        map: MonolithMap,
+        options: &SolverOptions,
+    ) -> Result<Vec<Tile>, MyError> {
+        if options.objectives.0.contains(&Objective::Target) && options.target.is_none() {
+            return Err(MyError::MissingTarget);
+        }
         match self {
-            SolvingMethods::Method1 => monolith_solver::solve_1(map),
-            SolvingMethods::Method2 => monolith_solver::solve_2(map),
-            SolvingMethods::Method3 => monolith_solver::solve_3(map),
-            SolvingMethods::Method4 => monolith_solver::solve_4(map),
-            SolvingMethods::Method5 => monolith_solver::solve_5(map),
-            SolvingMethods::Method6 => monolith_solver::solve_6(map),
-            SolvingMethods::Method7 => monolith_solver::solve_7(map),
-            SolvingMethods::Method8 => monolith_solver::solve_8(map),
-            SolvingMethods::Method9 => monolith_solver::solve_9(map),
-            SolvingMethods::Method10 => monolith_solver::solve_10(map),
-            SolvingMethods::Method11 => monolith_solver::solve_11(map),
+            SolvingMethods::Method1 => monolith_solver::solve_1(map, options),
+            SolvingMethods::Method2 => monolith_solver::solve_2(map, options),
+            SolvingMethods::Method3 => monolith_solver::solve_3(map, options),
+            SolvingMethods::Method4 => monolith_solver::solve_4(map, options),
+            SolvingMethods::Method5 => monolith_solver::solve_5(map, options),
+            SolvingMethods::Method6 => monolith_solver::solve_6(map, options),
+            SolvingMethods::Method7 => monolith_solver::solve_7(map, options),
+            SolvingMethods::Method8 => monolith_solver::solve_8(map, options),
+            SolvingMethods::Method9 => monolith_solver::solve_9(map, options),
+            SolvingMethods::Method10 => monolith_solver::solve_10(map, options),
+            SolvingMethods::Method11 => monolith_solver::solve_11(map, options),
         }
     }
 }
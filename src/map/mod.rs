@@ -1,6 +1,20 @@
+mod heuristic;
+mod hint;
 mod methods;
 mod monolith_map;
 mod monolith_solver;
+mod objective;
+mod replay;
+mod score;
+mod target;
+mod text_map;
 
-pub use methods::SolvingMethods;
-pub use monolith_map::{MonolithMap, Tile};
+pub use heuristic::HeuristicWeights;
+pub use hint::{get_hints, Hint};
+pub use methods::{SolverOptions, SolvingMethods};
+pub use monolith_map::{MonolithMap, Tile, MAX_TILE, MAX_X, MAX_Y};
+pub use objective::{get_mouse_travel, Objective, Objectives, Ranking, SolvedPath};
+pub use replay::{find_clicks, replay_game, PlayedClick, Replay, DEFAULT_MAX_CLICKS};
+pub use score::ScoreRules;
+pub use target::TargetSpec;
+pub use text_map::{MapFormat, TextMap};
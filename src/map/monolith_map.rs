@@ -1,27 +1,74 @@
 use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::path::Path;
 
 use super::SolvingMethods;
+use crate::errors::{MyError, WithPath};
+use crate::map_file::MapFile;
 
 pub type Tile = (usize, usize);
 
-pub type SolvedPath = (u32, Vec<Tile>);
-
-const MAX_X: usize = 22;
-const MAX_Y: usize = 11;
+pub const MAX_X: usize = 22;
+pub const MAX_Y: usize = 11;
+/// Largest tile type, cleared cells are 0.
+pub const MAX_TILE: u8 = 4;
 
+/// Rows of tiles. Deserialised boards are validated, see `TryFrom`.
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(try_from = "Vec<Vec<serde_json::Value>>")]
 pub struct MonolithMap(pub [[u8; MAX_X]; MAX_Y]);
 
 impl Default for MonolithMap {
     fn default() -> Self {
-        MonolithMap {
-            0: [[0; MAX_X]; MAX_Y],
+        MonolithMap([[0; MAX_X]; MAX_Y])
+    }
+}
+
+/// Board from JSON rows, with the first row, column or cell which isn't valid as
+/// the error.
+impl TryFrom<Vec<Vec<serde_json::Value>>> for MonolithMap {
+    type Error = MyError;
+
+    fn try_from(rows: Vec<Vec<serde_json::Value>>) -> Result<Self, Self::Error> {
+        if rows.len() != MAX_Y {
+            return Err(MyError::InvalidMapSize(rows.len()));
+        }
+        let mut map = MonolithMap::default();
+        for (y, row) in rows.iter().enumerate() {
+            if row.len() != MAX_X {
+                return Err(MyError::InvalidRowLength {
+                    row: y,
+                    columns: row.len(),
+                });
+            }
+            for (x, value) in row.iter().enumerate() {
+                match value.as_u64() {
+                    Some(tile) if tile <= u64::from(MAX_TILE) => map.set(x, y, tile as u8),
+                    _ => {
+                        return Err(MyError::InvalidTile {
+                            x,
+                            y,
+                            value: value.to_string(),
+                        })
+                    }
+                }
+            }
         }
+        Ok(map)
     }
 }
 
 impl MonolithMap {
-    pub fn solve(self, method: SolvingMethods) -> Vec<Tile> {
+    /// Loads the board of a `.map` file in any format, refusing boards of the wrong size,
+    /// with values which aren't tiles or still marked as guessed. See `MapFile` for the
+    /// metadata.
+    pub fn load(path: &Path) -> Result<MonolithMap, MyError> {
+        let map_file = MapFile::load(path)?;
+        map_file.check_verified().with_path(path)?;
+        Ok(map_file.tiles)
+    }
+
+    pub fn solve(self, method: SolvingMethods) -> Result<Vec<Tile>, MyError> {
         method.solve(self)
     }
 
@@ -29,11 +76,40 @@ impl MonolithMap {
         self.0[y][x]
     }
 
+    /// Sets the cell, `value` has to be a tile type, see `try_set`.
     pub fn set(&mut self, x: usize, y: usize, value: u8) {
-        debug_assert!(value <= 4);
+        debug_assert!(value <= MAX_TILE);
         self.0[y][x] = value;
     }
 
+    /// Board for tests, with `tile(x, y)` in the given rows and cleared cells elsewhere.
+    #[cfg(test)]
+    pub(crate) fn from_fn(
+        rows: std::ops::Range<usize>,
+        tile: impl Fn(usize, usize) -> usize,
+    ) -> MonolithMap {
+        let mut map = MonolithMap::default();
+        for y in rows {
+            for x in 0..MAX_X {
+                map.set(x, y, tile(x, y) as u8);
+            }
+        }
+        map
+    }
+
+    /// Sets the cell, or fails if `value` isn't a tile type.
+    pub fn try_set(&mut self, x: usize, y: usize, value: u8) -> Result<(), MyError> {
+        if value > MAX_TILE {
+            return Err(MyError::InvalidTile {
+                x,
+                y,
+                value: value.to_string(),
+            });
+        }
+        self.set(x, y, value);
+        Ok(())
+    }
+
     pub fn click(&mut self, x: usize, y: usize) {
         if self.get(x, y) == 0 {
             return;
@@ -165,7 +241,7 @@ impl MonolithMap {
             || (x < MAX_X - 1 && self.get(x + 1, y) == group_type)
     }
 
-    fn get_group(&self, x: usize, y: usize) -> Vec<Tile> {
+    pub fn get_group(&self, x: usize, y: usize) -> Vec<Tile> {
         let mut group = Vec::with_capacity(20);
         let mut visited = [[false; MAX_Y]; MAX_X];
 
@@ -289,7 +365,7 @@ impl MonolithMap {
         new_map
     }
 
-    fn get_all_tiles(&self) -> Vec<Tile> {
+    pub fn get_all_tiles(&self) -> Vec<Tile> {
         let mut result = Vec::with_capacity(264);
 
         for x in 0..MAX_X {
@@ -315,7 +391,7 @@ impl MonolithMap {
         result
     }
 
-    fn get_single_tiles(&self) -> Vec<Tile> {
+    pub fn get_single_tiles(&self) -> Vec<Tile> {
         let mut result = Vec::with_capacity(30);
 
         for x in 0..MAX_X {
@@ -370,8 +446,18 @@ impl MonolithMap {
 }
 
 #[cfg(test)]
+#[allow(clippy::init_numbered_fields, clippy::bool_assert_comparison)]
 mod test {
-    use super::{MonolithMap, SolvingMethods, Tile};
+    use super::{MonolithMap, SolvingMethods, Tile, MAX_TILE, MAX_X, MAX_Y};
+    use crate::errors::MyError;
+    use crate::map::SolverOptions;
+    use std::convert::TryFrom;
+    use std::time::{Duration, Instant};
+
+    /// Board in the text format, `.` is a cleared cell.
+    fn parse_map(text: &str) -> MonolithMap {
+        text.parse().unwrap()
+    }
 
     #[test]
     fn test_solve_1_step(){
@@ -390,7 +476,7 @@ mod test {
                 [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0], // 10
             ]
         };
-        let steps = map.solve(SolvingMethods::default());
+        let steps = map.solve(SolvingMethods::default()).unwrap();
         assert_eq!(steps.len(), 1);
         let correct_step_1: Vec<Tile> = vec![(8, 8), (8, 7), (8, 6)];
         assert!(correct_step_1.contains(&steps[0]));
@@ -413,7 +499,7 @@ mod test {
                 [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0], // 10
             ]
         };
-        let steps = map.solve(SolvingMethods::default());
+        let steps = map.solve(SolvingMethods::default()).unwrap();
         assert_eq!(steps.len(), 2);
         let correct_step_1: Vec<Tile> = vec![(8, 8), (9, 8)];
         assert!(correct_step_1.contains(&steps[0]));
@@ -438,7 +524,7 @@ mod test {
                 [0,0,4,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0], // 10
             ]
         };
-        let steps = map.solve(SolvingMethods::default());
+        let steps = map.solve(SolvingMethods::default()).unwrap();
         assert_eq!(steps.len(), 2);
         let correct_step_1: Vec<Tile> = vec![(8, 8), (9, 8)];
         assert!(correct_step_1.contains(&steps[0]));
@@ -446,6 +532,124 @@ mod test {
         assert!(correct_step_2.contains(&steps[1]));
     }
 
+    #[test]
+    fn test_validate_map() {
+        let mut rows = vec![vec![serde_json::json!(1); MAX_X]; MAX_Y];
+        assert!(MonolithMap::try_from(rows.clone()).is_ok());
+
+        rows[3][5] = serde_json::json!(7);
+        let json = serde_json::to_string(&rows).unwrap();
+        let error = MonolithMap::try_from(rows.clone()).unwrap_err();
+        assert!(matches!(error, MyError::InvalidTile { x: 5, y: 3, ref value } if value == "7"));
+        let error = serde_json::from_str::<MonolithMap>(&json).unwrap_err();
+        assert!(error.to_string().starts_with("row 3, column 5 has the value 7"));
+
+        for value in &[
+            serde_json::json!(-1),
+            serde_json::json!(1.5),
+            serde_json::json!("a"),
+        ] {
+            rows[3][5] = value.clone();
+            assert!(matches!(
+                MonolithMap::try_from(rows.clone()),
+                Err(MyError::InvalidTile { x: 5, y: 3, .. })
+            ));
+        }
+
+        rows[3][5] = serde_json::json!(0);
+        rows[2].pop();
+        assert!(matches!(
+            MonolithMap::try_from(rows.clone()),
+            Err(MyError::InvalidRowLength { row: 2, columns: 21 })
+        ));
+        rows.pop();
+        assert!(matches!(
+            MonolithMap::try_from(rows),
+            Err(MyError::InvalidMapSize(10))
+        ));
+
+        let map = MonolithMap([[2; MAX_X]; MAX_Y]);
+        let json = serde_json::to_string(&map).unwrap();
+        assert_eq!(serde_json::from_str::<MonolithMap>(&json).unwrap(), map);
+
+        let mut map = MonolithMap::default();
+        assert!(map.try_set(4, 2, MAX_TILE).is_ok());
+        assert_eq!(map.get(4, 2), MAX_TILE);
+        let error = map.try_set(4, 2, MAX_TILE + 1).unwrap_err();
+        assert!(matches!(error, MyError::InvalidTile { x: 4, y: 2, .. }));
+        assert_eq!(map.get(4, 2), MAX_TILE);
+    }
+
+    #[test]
+    fn test_solve_errors() {
+        let result = MonolithMap::default().solve(SolvingMethods::Method9);
+        assert!(matches!(result, Err(MyError::MissingTarget)));
+        let options = SolverOptions {
+            objectives: "target,tiles".parse().unwrap(),
+            ..SolverOptions::default()
+        };
+        let result = SolvingMethods::Method10.solve_with(MonolithMap::default(), &options);
+        assert!(matches!(result, Err(MyError::MissingTarget)));
+
+        // two rows of pairs, one part of the board with 22 groups
+        let map = MonolithMap::from_fn(0..2, |x, y| (x / 2 + y) % 2 + 1);
+        let result = map.solve(SolvingMethods::Method8);
+        assert!(matches!(
+            result,
+            Err(MyError::ClusterTooLarge {
+                tiles: 44,
+                groups: 22
+            })
+        ));
+    }
+
+    #[test]
+    fn test_time_limit() {
+        let map = parse_map("
+            2244433143423443343414
+            4331342444212124443234
+            1343232123313342322131
+            2211211411321121112122
+            2344141434343434441214
+            3441433441334432332223
+            3232212124222321141324
+            1211233212111211324113
+            4313433233124423344313
+            4421341444142433143242
+            1322221131221211221124
+        ");
+        let options = SolverOptions {
+            time_limit: Duration::from_millis(200),
+            ..SolverOptions::default()
+        };
+        let methods = [
+            SolvingMethods::Method1,
+            SolvingMethods::Method2,
+            SolvingMethods::Method3,
+            SolvingMethods::Method4,
+            SolvingMethods::Method11,
+        ];
+        // nothing is pruned with the clicks first
+        let clicks_first = SolverOptions {
+            objectives: "clicks,tiles".parse().unwrap(),
+            ..options.clone()
+        };
+        // without the limit the search takes minutes, the margin is for busy machines
+        for options in [&options, &clicks_first].iter() {
+            for method in methods.iter() {
+                let start = Instant::now();
+                let steps = method.clone().solve_with(map.clone(), options).unwrap();
+                assert!(start.elapsed() < Duration::from_secs(30), "{:?}", method);
+
+                let mut played = map.clone();
+                for step in steps {
+                    assert!(played.get_group(step.0, step.1).len() >= 2, "{:?}", method);
+                    played.click(step.0, step.1);
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_advance_1_tile(){
         let mut map = MonolithMap{
@@ -541,6 +745,41 @@ mod test {
         assert_eq!(map_after, map_after_after);
     }
 
+    #[test]
+    fn test_click_tile_text() {
+        // the board of `test_click_tile` in the text format
+        let mut map = parse_map("
+            ......................
+            ......................
+            ......................
+            .........44...........
+            .........213..........
+            .........213..........
+            ..........2...........
+            ......................
+            ......................
+            ......................
+            ......................
+        ");
+
+        map.click(10, 4);
+
+        let map_after = parse_map("
+            ......................
+            ......................
+            ......................
+            .........41...........
+            .........3.4..........
+            .........3.4..........
+            ..........3...........
+            ......................
+            ......................
+            ......................
+            ......................
+        ");
+        assert_eq!(map, map_after);
+    }
+
     #[test]
     fn test_click_tile_corner(){
         let mut map = MonolithMap{
@@ -1,24 +1,99 @@
-use super::monolith_map::{MonolithMap, SolvedPath, Tile};
-use crossbeam::queue::ArrayQueue;
+use super::heuristic::HeuristicWeights;
+use super::methods::SolverOptions;
+use super::monolith_map::{MonolithMap, Tile};
+use super::objective::{Ranking, SolvedPath};
+use super::target::TargetSpec;
+use crate::errors::MyError;
+use crossbeam::queue::{ArrayQueue, PushError};
 use rand::rngs::ThreadRng;
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 use std::borrow::Borrow;
-use std::convert::TryInto;
-use std::io::Read;
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
-use std::sync::{Arc, Mutex};
-use std::thread;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant};
 
+/// Locks the results. A worker which panicked while holding the lock is reported by
+/// `join_all`, the results themselves are still valid.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|error| error.into_inner())
+}
+
+/// Waits for all threads, fails if any of them panicked.
+fn join_all<T>(handles: Vec<JoinHandle<T>>) -> Result<Vec<T>, MyError> {
+    let mut results = Vec::with_capacity(handles.len());
+    let mut panicked = false;
+    for handle in handles {
+        match handle.join() {
+            Ok(result) => results.push(result),
+            Err(_) => panicked = true,
+        }
+    }
+    if panicked {
+        Err(MyError::WorkerPanicked)
+    } else {
+        Ok(results)
+    }
+}
+
+/// Waits for the timer and the workers. When the timer panicked, the workers are
+/// stopped before the error is returned, so no search keeps running.
+fn join_with_timer<T>(
+    timer: JoinHandle<()>,
+    workers: Vec<JoinHandle<T>>,
+    exit_flag: &AtomicBool,
+) -> Result<Vec<T>, MyError> {
+    let timer = join_all(vec![timer]);
+    if timer.is_err() {
+        exit_flag.store(true, Ordering::Release);
+    }
+    let results = join_all(workers)?;
+    timer.map(|_| results)
+}
+
+/// When a search stops and returns the best path found so far, see
+/// `SolverOptions::time_limit`.
+#[derive(Debug, Clone, Copy)]
+struct Deadline {
+    start: Instant,
+    time_limit: Duration,
+}
+
+impl Deadline {
+    fn new(time_limit: Duration) -> Deadline {
+        Deadline {
+            start: Instant::now(),
+            time_limit,
+        }
+    }
+
+    fn has_passed(&self) -> bool {
+        self.start.elapsed() > self.time_limit
+    }
+}
+
+/// Records the path if it is better than the last recorded one.
+fn record_result(
+    result: &Mutex<Vec<SolvedPath>>,
+    candidate: SolvedPath,
+    current_best: &AtomicU32,
+    ranking: &Ranking,
+) {
+    let mut results = lock(result);
+    if ranking.is_better(&candidate, results.last()) {
+        current_best.store(candidate.count, Ordering::Release);
+        println!(
+            "Current best result is: {} tiles remaining.",
+            candidate.count
+        );
+        results.push(candidate);
+    }
+}
+
 /// Recursive Random Singlethreaded Unbounbed Bruteforce
-pub fn solve_1(map: MonolithMap) -> Vec<Tile> {
-    fn random_walk(
-        results: &mut Vec<SolvedPath>,
-        steps: &mut Vec<Tile>,
-        map: &mut MonolithMap,
-        rng: &mut ThreadRng,
-    ) -> u32 {
+pub fn solve_1(map: MonolithMap, options: &SolverOptions) -> Result<Vec<Tile>, MyError> {
+    fn random_walk(steps: &mut Vec<Tile>, map: &mut MonolithMap, rng: &mut ThreadRng) -> u32 {
         let mut groups = map.all_groups();
         if groups.is_empty() {
             map.get_dead_tiles_count()
@@ -27,25 +102,28 @@ pub fn solve_1(map: MonolithMap) -> Vec<Tile> {
             let first_tile = groups[0][0];
             map.click(first_tile.0, first_tile.1);
             steps.push(first_tile);
-            random_walk(results, steps, map, rng)
+            random_walk(steps, map, rng)
         }
     }
 
+    let ranking = options.ranking(&map);
     let mut results: Vec<SolvedPath> = Vec::with_capacity(100);
     let mut rng = thread_rng();
     let start = Instant::now();
+    let deadline = Deadline::new(options.time_limit);
     loop {
         let mut steps = Vec::with_capacity(100);
-        let count = random_walk(&mut results, &mut steps, &mut map.clone(), &mut rng);
-        if results.is_empty() || count < results.first().unwrap().0 {
+        let count = random_walk(&mut steps, &mut map.clone(), &mut rng);
+        let result = ranking.path(count, steps);
+        if ranking.is_better(&result, results.first()) {
             println!("Found result with {} tiles remaining.", count);
-            results.push((count, steps));
-            results.sort();
+            results.push(result);
+            results.sort_by(|a, b| ranking.compare(a, b));
         };
-        if count == 0 {
+        if ranking.is_final(count) || deadline.has_passed() {
             break;
         } else {
-            let best = results.first().unwrap().0;
+            let best = results.first().map_or(count, |x| x.count);
             let elapsed = start.elapsed().as_secs();
             if elapsed > 10 && best < 5
                 || elapsed > 30 && best < 8
@@ -57,28 +135,32 @@ pub fn solve_1(map: MonolithMap) -> Vec<Tile> {
             }
         }
     }
-    results.reverse();
-    results.pop().unwrap_or_default().1
+    Ok(ranking.best(results).steps)
 }
 
 /// Recursive Sequential Singlethreaded Bounded Bruteforce
-pub fn solve_2(map: MonolithMap) -> Vec<Tile> {
+pub fn solve_2(map: MonolithMap, options: &SolverOptions) -> Result<Vec<Tile>, MyError> {
     fn work(
         results: &mut Vec<SolvedPath>,
         steps: Vec<Tile>,
         map: MonolithMap,
         dead_tiles_limit: u32,
+        ranking: &Ranking,
+        deadline: &Deadline,
     ) {
         let groups = map.all_groups();
         if groups.is_empty() {
-            let count = map.get_dead_tiles_count();
+            let result = ranking.path(map.get_dead_tiles_count(), steps);
 
-            if results.is_empty() || count < results.first().unwrap().0 {
-                results.push((count, steps));
-                results.sort();
+            if ranking.is_better(&result, results.first()) {
+                results.push(result);
+                results.sort_by(|a, b| ranking.compare(a, b));
             }
         } else {
             for group in groups {
+                if deadline.has_passed() {
+                    return;
+                }
                 let first_tile = group[0];
 
                 let mut new_map = map.clone();
@@ -92,11 +174,20 @@ pub fn solve_2(map: MonolithMap) -> Vec<Tile> {
                     temp.push(first_tile);
                     temp
                 };
-                work(results, new_steps, new_map, dead_tiles_limit);
+                work(
+                    results,
+                    new_steps,
+                    new_map,
+                    dead_tiles_limit,
+                    ranking,
+                    deadline,
+                );
             }
         }
     }
 
+    let ranking = options.ranking(&map);
+    let deadline = Deadline::new(options.time_limit);
     let mut results: Vec<SolvedPath> = Vec::with_capacity(100);
     for max_dead_tiles_allowed in [0u32, 5, 10, 15, 20].iter() {
         println!(
@@ -104,24 +195,34 @@ pub fn solve_2(map: MonolithMap) -> Vec<Tile> {
             max_dead_tiles_allowed
         );
         let map = map.clone();
-        work(&mut results, Vec::new(), map, *max_dead_tiles_allowed);
-        if !results.is_empty() {
+        work(
+            &mut results,
+            Vec::new(),
+            map,
+            *max_dead_tiles_allowed,
+            &ranking,
+            &deadline,
+        );
+        if !results.is_empty() || deadline.has_passed() {
             break;
         }
     }
-    results.reverse();
-    results.pop().unwrap_or_default().1
+    Ok(ranking.best(results).steps)
 }
 
 /// Recursive Singlethreaded N-Step Lookahead
-pub fn solve_3(map: MonolithMap) -> Vec<Tile> {
-    type SolvedPath = (u32, Vec<Tile>);
-
-    fn walk(map: MonolithMap, steps: Vec<Tile>, depth_to_go: u32) -> SolvedPath {
+pub fn solve_3(map: MonolithMap, options: &SolverOptions) -> Result<Vec<Tile>, MyError> {
+    fn walk(
+        map: MonolithMap,
+        steps: Vec<Tile>,
+        depth_to_go: u32,
+        ranking: &Ranking,
+        deadline: &Deadline,
+    ) -> SolvedPath {
         let groups = map.all_groups();
         if groups.is_empty() {
             let count = map.get_dead_tiles_count();
-            (count, steps)
+            ranking.path(count, steps)
         } else {
             let mut results = Vec::<SolvedPath>::with_capacity(100);
             for group in groups {
@@ -136,48 +237,59 @@ pub fn solve_3(map: MonolithMap) -> Vec<Tile> {
                     temp
                 };
 
-                if depth_to_go == 0 {
+                // after the deadline the lookahead ends here
+                if depth_to_go == 0 || deadline.has_passed() {
                     let count = map.get_dead_tiles_count();
-                    results.push((count, new_steps));
+                    results.push(ranking.path(count, new_steps));
                 } else {
-                    let (count, best_steps) = walk(new_map, new_steps, depth_to_go - 1);
-                    results.push((count, best_steps));
+                    results.push(walk(new_map, new_steps, depth_to_go - 1, ranking, deadline));
                 }
             }
-            results.sort();
-            results.reverse();
-            results.pop().unwrap_or_default()
+            ranking.best(results)
         }
     }
+    let ranking = options.ranking(&map);
+    let deadline = Deadline::new(options.time_limit);
     let mut results = Vec::<SolvedPath>::with_capacity(5);
 
     for lookahead in &[0, 1, 2, 3, 4] {
         println!("Trying {}-step lookahead...", lookahead);
         let new_map = map.clone();
-        let (count, steps) = walk(new_map, Vec::with_capacity(50), *lookahead);
-        results.push((count, steps));
-        println!("Found result with {} tiles remaining.", count);
+        let result = walk(
+            new_map,
+            Vec::with_capacity(50),
+            *lookahead,
+            &ranking,
+            &deadline,
+        );
+        println!("Found result with {} tiles remaining.", result.count);
+        results.push(result);
+        if deadline.has_passed() {
+            break;
+        }
     }
-    results.sort();
-    results.reverse();
-    results.pop().unwrap_or_default().1
+    Ok(ranking.best(results).steps)
 }
 
 /// Recursive Sequential Multithreaded Bruteforce
-pub fn solve_4(map: MonolithMap) -> Vec<Tile> {
+pub fn solve_4(map: MonolithMap, options: &SolverOptions) -> Result<Vec<Tile>, MyError> {
     fn brute_solver(
         job_queue: Arc<ArrayQueue<(Vec<Tile>, MonolithMap)>>,
         result_queue: Arc<ArrayQueue<(u32, Vec<Tile>)>>,
-    ) {
+        deadline: Deadline,
+    ) -> Result<(), MyError> {
         let max_dead_tiles_allowed = 20;
         loop {
+            if deadline.has_passed() {
+                return Ok(());
+            }
             let (steps, map) = match job_queue.pop() {
                 Ok(job) => job,
                 Err(_) => {
                     thread::sleep(Duration::from_millis(1_000));
                     match job_queue.pop() {
                         Ok(job) => job,
-                        Err(_) => return,
+                        Err(_) => return Ok(()),
                     }
                 }
             };
@@ -189,7 +301,7 @@ pub fn solve_4(map: MonolithMap) -> Vec<Tile> {
                 if result_queue.is_empty() || count < max_dead_tiles_allowed {
                     let res = result_queue.push((count, steps));
                     if res.is_err() {
-                        return;
+                        return Ok(());
                     }
                 }
             } else {
@@ -199,49 +311,60 @@ pub fn solve_4(map: MonolithMap) -> Vec<Tile> {
                     new_steps.push(first_tile);
                     let mut new_map = map.clone();
                     new_map.click(first_tile.0, first_tile.1);
-                    while job_queue.is_full() {
+                    // other workers can fill the queue between a check and the push,
+                    // so a failed push waits for a free slot and tries again
+                    let mut job = (new_steps, new_map);
+                    while let Err(PushError(rejected)) = job_queue.push(job) {
+                        if deadline.has_passed() {
+                            return Ok(());
+                        }
                         thread::sleep(Duration::from_millis(100));
+                        job = rejected;
                     }
-                    job_queue
-                        .push((new_steps, new_map))
-                        .expect("Failed to push a new job.");
                 }
             }
         }
-    };
+    }
+    let ranking = options.ranking(&map);
     let job_queue = Arc::new(ArrayQueue::new(1000));
     job_queue
         .push((Vec::<Tile>::new(), map))
-        .expect("Failed to push starting value.");
+        .map_err(|_| MyError::QueueOverflow)?;
     let result_queue = Arc::new(ArrayQueue::new(100));
+    let deadline = Deadline::new(options.time_limit);
 
     let workers: Vec<_> = (1..16)
         .map(|_| {
             let q1 = job_queue.clone();
             let q2 = result_queue.clone();
-            thread::spawn(|| brute_solver(q1, q2))
+            thread::spawn(move || brute_solver(q1, q2, deadline))
         })
         .collect();
 
-    for worker in workers {
-        worker.join().expect("Failed to join on a thread handle.");
+    for result in join_all(workers)? {
+        result?;
     }
 
     let mut results = Vec::new();
-    while let Ok(value) = result_queue.pop() {
-        results.push(value);
+    while let Ok((count, steps)) = result_queue.pop() {
+        results.push(ranking.path(count, steps));
     }
-    results.sort();
-    results.reverse();
-    results.pop().unwrap_or_default().1
+    Ok(ranking.best(results).steps)
 }
 
 /// Recursive Sequential Multithreaded Bruteforce
-pub fn solve_5(map: MonolithMap) -> Vec<Tile> {
-    fn timer_thread(exit_flag: Arc<AtomicBool>, current_best: Arc<AtomicU32>) {
+pub fn solve_5(map: MonolithMap, options: &SolverOptions) -> Result<Vec<Tile>, MyError> {
+    fn timer_thread(
+        exit_flag: Arc<AtomicBool>,
+        current_best: Arc<AtomicU32>,
+        ranking: Ranking,
+        time_limit: Duration,
+    ) {
         let start = Instant::now();
         loop {
-            if start.elapsed().as_secs() > 60 || current_best.load(Ordering::Relaxed) == 0 {
+            if start.elapsed() > time_limit
+                || ranking.is_final(current_best.load(Ordering::Relaxed))
+            {
                 println!("Stopping solver.");
                 exit_flag.store(true, Ordering::Release);
                 break;
@@ -254,6 +377,7 @@ pub fn solve_5(map: MonolithMap) -> Vec<Tile> {
         result: Arc<Mutex<Vec<SolvedPath>>>,
         current_best: Arc<AtomicU32>,
         exit_flag: Arc<AtomicBool>,
+        ranking: Ranking,
     ) {
         fn work(
             result: &Mutex<Vec<SolvedPath>>,
@@ -261,6 +385,7 @@ pub fn solve_5(map: MonolithMap) -> Vec<Tile> {
             map: MonolithMap,
             current_best: &AtomicU32,
             exit_flag: &AtomicBool,
+            ranking: &Ranking,
         ) {
             if exit_flag.load(Ordering::Acquire) {
                 return;
@@ -270,10 +395,8 @@ pub fn solve_5(map: MonolithMap) -> Vec<Tile> {
             if groups.is_empty() {
                 let count = map.get_dead_tiles_count();
 
-                if count < current_best.load(Ordering::Acquire) {
-                    result.lock().unwrap().push((count, steps));
-                    current_best.store(count, Ordering::Release);
-                    println!("Current best result is: {} tiles remaining.", count);
+                if !ranking.prunes(count, current_best.load(Ordering::Acquire)) {
+                    record_result(result, ranking.path(count, steps), current_best, ranking);
                 }
             } else {
                 for group in groups {
@@ -281,7 +404,10 @@ pub fn solve_5(map: MonolithMap) -> Vec<Tile> {
 
                     let mut new_map = map.clone();
                     new_map.click(first_tile.0, first_tile.1);
-                    if new_map.get_dead_tiles_count() >= current_best.load(Ordering::Acquire) {
+                    if ranking.prunes(
+                        new_map.get_dead_tiles_count(),
+                        current_best.load(Ordering::Acquire),
+                    ) {
                         continue;
                     }
 
@@ -290,7 +416,7 @@ pub fn solve_5(map: MonolithMap) -> Vec<Tile> {
                         temp.push(first_tile);
                         temp
                     };
-                    work(result, new_steps, new_map, current_best, exit_flag);
+                    work(result, new_steps, new_map, current_best, exit_flag, ranking);
                 }
             }
         }
@@ -315,9 +441,10 @@ pub fn solve_5(map: MonolithMap) -> Vec<Tile> {
                 map,
                 current_best.borrow(),
                 exit_flag.borrow(),
+                &ranking,
             );
         }
-    };
+    }
 
     let job_queue = Arc::new(ArrayQueue::new(200));
     let result = Arc::new(Mutex::new(Vec::with_capacity(100)));
@@ -333,14 +460,17 @@ pub fn solve_5(map: MonolithMap) -> Vec<Tile> {
             new_map.click(first_tile.0, first_tile.1);
             job_queue
                 .push((new_steps, new_map))
-                .expect("Failed to push starting value.");
+                .map_err(|_| MyError::QueueOverflow)?;
         }
     }
 
+    let ranking = options.ranking(&map);
     let timer_handle = {
         let exit_flag_clone = exit_flag.clone();
         let best_clone = current_best.clone();
-        thread::spawn(|| timer_thread(exit_flag_clone, best_clone))
+        let ranking_clone = ranking.clone();
+        let time_limit = options.time_limit;
+        thread::spawn(move || timer_thread(exit_flag_clone, best_clone, ranking_clone, time_limit))
     };
     let workers: Vec<_> = (0..8)
         .map(|_| {
@@ -348,29 +478,38 @@ pub fn solve_5(map: MonolithMap) -> Vec<Tile> {
             let result_clone = result.clone();
             let best_clone = current_best.clone();
             let exit_flag_clone = exit_flag.clone();
-            thread::spawn(|| brute_solver(job_clone, result_clone, best_clone, exit_flag_clone))
+            let ranking_clone = ranking.clone();
+            thread::spawn(|| {
+                brute_solver(
+                    job_clone,
+                    result_clone,
+                    best_clone,
+                    exit_flag_clone,
+                    ranking_clone,
+                )
+            })
         })
         .collect();
 
-    timer_handle
-        .join()
-        .expect("Failed to join on a timer thread handle.");
-    for worker in workers {
-        worker.join().expect("Failed to join on a thread handle.");
-    }
+    join_with_timer(timer_handle, workers, &exit_flag)?;
 
-    let mut results = result.lock().unwrap();
-    results.sort();
-    results.reverse();
-    results.pop().unwrap_or_default().1
+    let results = lock(&result).drain(..).collect();
+    Ok(ranking.best(results).steps)
 }
 
 /// Recursive Random Multithreaded Bounbed Bruteforce
-pub fn solve_6(map: MonolithMap) -> Vec<Tile> {
-    fn timer_thread(exit_flag: Arc<AtomicBool>, current_best: Arc<AtomicU32>) {
+pub fn solve_6(map: MonolithMap, options: &SolverOptions) -> Result<Vec<Tile>, MyError> {
+    fn timer_thread(
+        exit_flag: Arc<AtomicBool>,
+        current_best: Arc<AtomicU32>,
+        ranking: Ranking,
+        time_limit: Duration,
+    ) {
         let start = Instant::now();
         loop {
-            if start.elapsed().as_secs() > 60 || current_best.load(Ordering::Relaxed) == 0 {
+            if start.elapsed() > time_limit
+                || ranking.is_final(current_best.load(Ordering::Relaxed))
+            {
                 println!("Stopping solver.");
                 exit_flag.store(true, Ordering::Relaxed);
                 break;
@@ -383,6 +522,7 @@ pub fn solve_6(map: MonolithMap) -> Vec<Tile> {
         result: Arc<Mutex<Vec<SolvedPath>>>,
         current_best: Arc<AtomicU32>,
         exit_flag: Arc<AtomicBool>,
+        ranking: Ranking,
     ) {
         fn work(
             result: &Mutex<Vec<SolvedPath>>,
@@ -391,6 +531,7 @@ pub fn solve_6(map: MonolithMap) -> Vec<Tile> {
             current_best: &AtomicU32,
             exit_flag: &AtomicBool,
             rng: &mut ThreadRng,
+            ranking: &Ranking,
         ) {
             if exit_flag.load(Ordering::Acquire) {
                 return;
@@ -400,10 +541,8 @@ pub fn solve_6(map: MonolithMap) -> Vec<Tile> {
             if groups.is_empty() {
                 let count = map.get_dead_tiles_count();
 
-                if count < current_best.load(Ordering::Acquire) {
-                    result.lock().unwrap().push((count, steps));
-                    current_best.store(count, Ordering::Release);
-                    println!("Current best result is: {} tiles remaining.", count);
+                if !ranking.prunes(count, current_best.load(Ordering::Acquire)) {
+                    record_result(result, ranking.path(count, steps), current_best, ranking);
                 }
             } else {
                 groups.shuffle(rng);
@@ -412,7 +551,10 @@ pub fn solve_6(map: MonolithMap) -> Vec<Tile> {
 
                     let mut new_map = map.clone();
                     new_map.click(first_tile.0, first_tile.1);
-                    if new_map.get_dead_tiles_count() >= current_best.load(Ordering::Acquire) {
+                    if ranking.prunes(
+                        new_map.get_dead_tiles_count(),
+                        current_best.load(Ordering::Acquire),
+                    ) {
                         continue;
                     }
 
@@ -421,7 +563,15 @@ pub fn solve_6(map: MonolithMap) -> Vec<Tile> {
                         temp.push(first_tile);
                         temp
                     };
-                    work(result, new_steps, new_map, current_best, exit_flag, rng);
+                    work(
+                        result,
+                        new_steps,
+                        new_map,
+                        current_best,
+                        exit_flag,
+                        rng,
+                        ranking,
+                    );
                 }
             }
         }
@@ -433,17 +583,21 @@ pub fn solve_6(map: MonolithMap) -> Vec<Tile> {
             current_best.borrow(),
             exit_flag.borrow(),
             &mut thread_rng(),
+            &ranking,
         );
-    };
+    }
 
     let result = Arc::new(Mutex::new(Vec::with_capacity(100)));
     let current_best = Arc::new(AtomicU32::new(22 * 11));
     let exit_flag = Arc::new(AtomicBool::new(false));
 
+    let ranking = options.ranking(&map);
     let timer_handle = {
         let exit_flag_clone = exit_flag.clone();
         let best_clone = current_best.clone();
-        thread::spawn(|| timer_thread(exit_flag_clone, best_clone))
+        let ranking_clone = ranking.clone();
+        let time_limit = options.time_limit;
+        thread::spawn(move || timer_thread(exit_flag_clone, best_clone, ranking_clone, time_limit))
     };
     let workers: Vec<_> = (0..8)
         .map(|_| {
@@ -451,29 +605,38 @@ pub fn solve_6(map: MonolithMap) -> Vec<Tile> {
             let result_clone = result.clone();
             let best_clone = current_best.clone();
             let exit_flag_clone = exit_flag.clone();
-            thread::spawn(|| brute_solver(map, result_clone, best_clone, exit_flag_clone))
+            let ranking_clone = ranking.clone();
+            thread::spawn(|| {
+                brute_solver(
+                    map,
+                    result_clone,
+                    best_clone,
+                    exit_flag_clone,
+                    ranking_clone,
+                )
+            })
         })
         .collect();
 
-    timer_handle
-        .join()
-        .expect("Failed to join on a timer thread handle.");
-    for worker in workers {
-        worker.join().expect("Failed to join on a thread handle.");
-    }
+    join_with_timer(timer_handle, workers, &exit_flag)?;
 
-    let mut results = result.lock().unwrap();
-    results.sort();
-    results.reverse();
-    results.pop().unwrap_or_default().1
+    let results = lock(&result).drain(..).collect();
+    Ok(ranking.best(results).steps)
 }
 
 /// Recursive Random SingleGroup Multithreaded Bounbed Bruteforce
-pub fn solve_7(map: MonolithMap) -> Vec<Tile> {
-    fn timer_thread(exit_flag: Arc<AtomicBool>, current_best: Arc<AtomicU32>) {
+pub fn solve_7(map: MonolithMap, options: &SolverOptions) -> Result<Vec<Tile>, MyError> {
+    fn timer_thread(
+        exit_flag: Arc<AtomicBool>,
+        current_best: Arc<AtomicU32>,
+        ranking: Ranking,
+        time_limit: Duration,
+    ) {
         let start = Instant::now();
         loop {
-            if start.elapsed().as_secs() > 60 || current_best.load(Ordering::Relaxed) == 0 {
+            if start.elapsed() > time_limit
+                || ranking.is_final(current_best.load(Ordering::Relaxed))
+            {
                 println!("Stopping solver.");
                 exit_flag.store(true, Ordering::Release);
                 break;
@@ -486,6 +649,7 @@ pub fn solve_7(map: MonolithMap) -> Vec<Tile> {
         result: Arc<Mutex<Vec<SolvedPath>>>,
         current_best: Arc<AtomicU32>,
         exit_flag: Arc<AtomicBool>,
+        ranking: Ranking,
     ) {
         fn random_walk(steps: &mut Vec<Tile>, map: &mut MonolithMap, rng: &mut ThreadRng) -> u32 {
             let mut groups = map.all_groups();
@@ -507,23 +671,29 @@ pub fn solve_7(map: MonolithMap) -> Vec<Tile> {
             }
             let count = random_walk(&mut steps, &mut map.clone(), &mut rng);
 
-            if count < current_best.load(Ordering::Acquire) {
-                result.lock().unwrap().push((count, steps.clone()));
-                current_best.store(count, Ordering::Release);
-                println!("Current best result is: {} tiles remaining.", count);
+            if !ranking.prunes(count, current_best.load(Ordering::Acquire)) {
+                record_result(
+                    &result,
+                    ranking.path(count, steps.clone()),
+                    &current_best,
+                    &ranking,
+                );
             }
             steps.clear();
         }
-    };
+    }
 
     let result = Arc::new(Mutex::new(Vec::with_capacity(100)));
     let current_best = Arc::new(AtomicU32::new(22 * 11));
     let exit_flag = Arc::new(AtomicBool::new(false));
 
+    let ranking = options.ranking(&map);
     let timer_handle = {
         let exit_flag_clone = exit_flag.clone();
         let best_clone = current_best.clone();
-        thread::spawn(|| timer_thread(exit_flag_clone, best_clone))
+        let ranking_clone = ranking.clone();
+        let time_limit = options.time_limit;
+        thread::spawn(move || timer_thread(exit_flag_clone, best_clone, ranking_clone, time_limit))
     };
     let workers: Vec<_> = (0..8)
         .map(|_| {
@@ -531,46 +701,60 @@ pub fn solve_7(map: MonolithMap) -> Vec<Tile> {
             let result_clone = result.clone();
             let best_clone = current_best.clone();
             let exit_flag_clone = exit_flag.clone();
-            thread::spawn(|| brute_solver(map, result_clone, best_clone, exit_flag_clone))
+            let ranking_clone = ranking.clone();
+            thread::spawn(|| {
+                brute_solver(
+                    map,
+                    result_clone,
+                    best_clone,
+                    exit_flag_clone,
+                    ranking_clone,
+                )
+            })
         })
         .collect();
 
-    timer_handle
-        .join()
-        .expect("Failed to join on a timer thread handle.");
-    for worker in workers {
-        worker.join().expect("Failed to join on a thread handle.");
-    }
+    join_with_timer(timer_handle, workers, &exit_flag)?;
 
-    let mut results = result.lock().unwrap();
-    results.sort();
-    results.reverse();
-    results.pop().unwrap_or_default().1
+    let results = lock(&result).drain(..).collect();
+    Ok(ranking.best(results).steps)
 }
 
-pub fn solve_8(map: MonolithMap) -> Vec<Tile> {
-    fn cluster_solver(map_queue: Arc<ArrayQueue<MonolithMap>>, result: Arc<Mutex<Vec<Tile>>>) {
+pub fn solve_8(map: MonolithMap, options: &SolverOptions) -> Result<Vec<Tile>, MyError> {
+    fn cluster_solver(
+        map_queue: Arc<ArrayQueue<MonolithMap>>,
+        result: Arc<Mutex<Vec<Tile>>>,
+        ranking: Ranking,
+        deadline: Deadline,
+    ) {
         fn work(
             results: &mut Vec<SolvedPath>,
             steps: Vec<Tile>,
             map: MonolithMap,
             current_best: &mut u32,
+            ranking: &Ranking,
+            deadline: &Deadline,
         ) {
             let groups = map.all_groups();
             if groups.is_empty() {
-                let count = map.get_dead_tiles_count();
+                let result = ranking.path(map.get_dead_tiles_count(), steps);
 
-                if count < *current_best {
-                    results.push((count, steps));
-                    *current_best = count;
+                if !ranking.prunes(result.count, *current_best)
+                    && ranking.is_better(&result, results.last())
+                {
+                    *current_best = result.count;
+                    results.push(result);
                 }
             } else {
                 for group in groups {
+                    if deadline.has_passed() {
+                        return;
+                    }
                     let first_tile = group[0];
 
                     let mut new_map = map.clone();
                     new_map.click(first_tile.0, first_tile.1);
-                    if new_map.get_dead_tiles_count() >= *current_best {
+                    if ranking.prunes(new_map.get_dead_tiles_count(), *current_best) {
                         continue;
                     }
 
@@ -579,10 +763,10 @@ pub fn solve_8(map: MonolithMap) -> Vec<Tile> {
                         temp.push(first_tile);
                         temp
                     };
-                    work(results, new_steps, new_map, current_best);
+                    work(results, new_steps, new_map, current_best, ranking, deadline);
                 }
             }
-        };
+        }
 
         loop {
             let map = match map_queue.pop() {
@@ -603,32 +787,39 @@ pub fn solve_8(map: MonolithMap) -> Vec<Tile> {
                 map_queue.len()
             );
 
+            let ranking = ranking.with_map(&map);
             let mut results = Vec::with_capacity(50);
             let mut best_result = tile_count;
-            work(&mut results, Vec::with_capacity(50), map, &mut best_result);
+            work(
+                &mut results,
+                Vec::with_capacity(50),
+                map,
+                &mut best_result,
+                &ranking,
+                &deadline,
+            );
             if !results.is_empty() {
-                results.sort();
-                results.reverse();
-                let (count, path) = results.pop().unwrap_or_default();
-                assert_eq!(count, best_result);
+                // with other objectives first the best path doesn't have to leave the
+                // fewest tiles
+                let best = ranking.best(results);
                 println!(
                     "Best result for cluster ({} tiles) is {} tiles remaining.",
-                    tile_count, count
+                    tile_count, best.count
                 );
 
                 {
-                    let mut result_vec = result.lock().unwrap();
-                    for step in path {
+                    let mut result_vec = lock(&result);
+                    for step in best.steps {
                         result_vec.push(step);
                     }
                 }
             }
         }
-    };
+    }
 
     let map_queue = Arc::new(ArrayQueue::new(50));
     let clusters = map.all_tile_clusters();
-    for (index, cluster) in clusters.into_iter().enumerate() {
+    for cluster in clusters {
         let cluster_map = map.create_map_from_cluster(&cluster);
         let all_groups = cluster_map.all_groups();
 
@@ -637,70 +828,47 @@ pub fn solve_8(map: MonolithMap) -> Vec<Tile> {
         }
 
         if all_groups.len() >= 12 {
-            println!(
-                "Cluster #{} ({} tiles) has too many groups in it ({}).",
-                index,
-                cluster.len(),
-                all_groups.len()
-            );
-            return Vec::new();
+            return Err(MyError::ClusterTooLarge {
+                tiles: cluster.len(),
+                groups: all_groups.len(),
+            });
         }
 
         map_queue
             .push(cluster_map)
-            .expect("Failed to push a starting cluster map.");
+            .map_err(|_| MyError::QueueOverflow)?;
     }
     let result = Arc::new(Mutex::new(Vec::with_capacity(100)));
+    let ranking = options.ranking(&map);
+    let deadline = Deadline::new(options.time_limit);
 
     let workers: Vec<_> = (0..8)
         .map(|_| {
             let q1 = map_queue.clone();
             let q2 = result.clone();
-            thread::spawn(|| cluster_solver(q1, q2))
+            let ranking_clone = ranking.clone();
+            thread::spawn(move || cluster_solver(q1, q2, ranking_clone, deadline))
         })
         .collect();
 
-    for worker in workers {
-        worker.join().expect("Failed to join on a thread handle.");
-    }
+    join_all(workers)?;
 
-    Arc::try_unwrap(result)
-        .expect("Arc had several owners.")
-        .into_inner()
-        .expect("Mutex was poisoned.")
+    let steps = lock(&result).drain(..).collect();
+    Ok(steps)
 }
 
-/// Find Solutions Where That Spot Is Empty
-pub fn solve_9(map: MonolithMap) -> Vec<Tile> {
-    fn load_target_tiles() -> Vec<Tile> {
-        let mut file = std::fs::File::open("tiles.txt").expect("Failed to open 'tiles.txt'.");
-        let mut buffer = String::new();
-        file.read_to_string(&mut buffer)
-            .expect("Failed to read from file.");
-        let mut tiles =
-            serde_json::from_str::<Vec<(usize, usize)>>(&buffer).expect("Failed to parse JSON.");
-        tiles.sort();
-        tiles.dedup();
-        tiles
-    }
-
-    fn get_map_diff_score(current_map: &MonolithMap, target_tiles: &[Tile]) -> u32 {
-        let mut score = 0;
-        for tile in target_tiles {
-            if current_map.get(tile.0, tile.1) == 0 {
-                score += 1;
-            }
-        }
-        score
-    }
+/// Find Solutions Matching The Target Pattern
+pub fn solve_9(map: MonolithMap, options: &SolverOptions) -> Result<Vec<Tile>, MyError> {
+    /// Target score of the board after the path, higher is better.
+    type TargetPath = (u32, Vec<Tile>);
 
-    fn timer_thread(exit_flag: Arc<AtomicBool>) {
+    fn timer_thread(exit_flag: Arc<AtomicBool>, time_limit: Duration) {
         let start = Instant::now();
         loop {
             if exit_flag.load(Ordering::Acquire) {
                 break;
             }
-            if start.elapsed().as_secs() > 60 {
+            if start.elapsed() > time_limit {
                 println!("Stopping solver.");
                 exit_flag.store(true, Ordering::Release);
                 break;
@@ -712,12 +880,12 @@ pub fn solve_9(map: MonolithMap) -> Vec<Tile> {
     fn random_walk(
         steps: &mut Vec<Tile>,
         map: &mut MonolithMap,
-        target: &[Tile],
+        target: &TargetSpec,
         rng: &mut ThreadRng,
     ) -> u32 {
         let mut groups = map.all_groups();
         if groups.is_empty() {
-            get_map_diff_score(map, target)
+            target.get_score(map)
         } else {
             groups.shuffle(rng);
             let first_tile = groups[0][0];
@@ -729,8 +897,8 @@ pub fn solve_9(map: MonolithMap) -> Vec<Tile> {
 
     fn brute_solver(
         map: MonolithMap,
-        target: Vec<Tile>,
-        result: Arc<Mutex<Vec<SolvedPath>>>,
+        target: TargetSpec,
+        result: Arc<Mutex<Vec<TargetPath>>>,
         current_best: Arc<AtomicU32>,
         exit_flag: Arc<AtomicBool>,
     ) {
@@ -743,40 +911,38 @@ pub fn solve_9(map: MonolithMap) -> Vec<Tile> {
             let count = random_walk(&mut steps, &mut map.clone(), &target, &mut rng);
 
             if count > current_best.load(Ordering::Acquire) {
-                let target_len: u32 = target
-                    .len()
-                    .try_into()
-                    .expect("Failed to convert usize to u32");
-                result.lock().unwrap().push((count, steps.clone()));
+                let max_score = target.get_max_score();
+                lock(&result).push((count, steps.clone()));
                 current_best.store(count, Ordering::Release);
                 println!(
-                    "Current best result is: {}/{} tiles freed.",
-                    count, target_len
+                    "Current best result is: {}/{} target score.",
+                    count, max_score
                 );
-                if count == target_len {
+                if count == max_score {
                     exit_flag.store(true, Ordering::Release);
                     break;
                 }
             }
             steps.clear();
         }
-    };
+    }
 
+    let target = options.target.clone().ok_or(MyError::MissingTarget)?;
     let exit_flag = Arc::new(AtomicBool::new(false));
 
     let timer_handle = {
         let exit_flag_clone = exit_flag.clone();
-        thread::spawn(|| timer_thread(exit_flag_clone))
+        let time_limit = options.time_limit;
+        thread::spawn(move || timer_thread(exit_flag_clone, time_limit))
     };
 
-    let target_tiles = load_target_tiles();
     let result = Arc::new(Mutex::new(Vec::with_capacity(100)));
     let current_best = Arc::new(AtomicU32::new(0));
 
     let workers: Vec<_> = (0..8)
         .map(|_| {
             let map = map.clone();
-            let target = target_tiles.clone();
+            let target = target.clone();
             let result_clone = result.clone();
             let best_clone = current_best.clone();
             let exit_flag_clone = exit_flag.clone();
@@ -784,29 +950,24 @@ pub fn solve_9(map: MonolithMap) -> Vec<Tile> {
         })
         .collect();
 
-    timer_handle
-        .join()
-        .expect("Failed to join on a timer thread handle.");
-    for worker in workers {
-        worker.join().expect("Failed to join on a thread handle.");
-    }
+    join_with_timer(timer_handle, workers, &exit_flag)?;
 
-    let mut results = result.lock().unwrap();
+    let mut results = lock(&result);
     results.sort();
-    results.pop().unwrap_or_default().1
+    Ok(results.pop().unwrap_or_default().1)
 }
 
 /// Using special function to find best groups to click
-pub fn solve_10(map: MonolithMap) -> Vec<Tile> {
-    fn get_group_score(original_map: &MonolithMap, group: &[Tile]) -> f64 {
+pub fn solve_10(map: MonolithMap, options: &SolverOptions) -> Result<Vec<Tile>, MyError> {
+    fn get_group_score(
+        original_map: &MonolithMap,
+        group: &[Tile],
+        weights: &HeuristicWeights,
+    ) -> f64 {
         let mut new_map = original_map.clone();
         let first_tile = group[0];
         new_map.click(first_tile.0, first_tile.1);
-        let new_groups = new_map.all_groups();
-        let groups_total_size = new_groups.iter().fold(0, |sum, e| sum + e.len());
-        // new_groups.len() as f64 // ?
-        // groups_total_size as f64 // ?
-        groups_total_size as f64 / new_groups.len() as f64 // ?
+        weights.evaluate(&new_map)
     }
     fn cmp_f64(a: f64, b: f64) -> std::cmp::Ordering {
         if a < b {
@@ -816,46 +977,47 @@ pub fn solve_10(map: MonolithMap) -> Vec<Tile> {
         }
         std::cmp::Ordering::Equal
     }
-    fn walk(steps: &mut Vec<Tile>, map: &mut MonolithMap) {
+    fn walk(steps: &mut Vec<Tile>, map: &mut MonolithMap, weights: &HeuristicWeights) {
         let groups = map.all_groups();
         if groups.is_empty() {
         } else if groups.len() == 1 {
             let first_tile = groups[0][0];
             map.click(first_tile.0, first_tile.1);
             steps.push(first_tile);
-            walk(steps, map)
+            walk(steps, map, weights)
         } else {
-            let best_group = {
-                let mut best_result = groups
-                    .into_iter()
-                    .map(|x| (get_group_score(map, &x), x))
-                    .collect::<Vec<_>>();
-                best_result.sort_by(|a, b| cmp_f64(a.0, b.0));
-                best_result.pop().unwrap().1
+            let best_group = groups
+                .into_iter()
+                .map(|x| (get_group_score(map, &x, weights), x))
+                .max_by(|a, b| cmp_f64(a.0, b.0));
+            let first_tile = match best_group {
+                Some((_, group)) => group[0],
+                None => return,
             };
-            let first_tile = best_group[0];
             map.click(first_tile.0, first_tile.1);
             steps.push(first_tile);
-            walk(steps, map)
+            walk(steps, map, weights)
         }
     }
 
     let mut steps = Vec::with_capacity(100);
-    walk(&mut steps, &mut map.clone());
-    steps
+    walk(&mut steps, &mut map.clone(), &options.weights);
+    Ok(steps)
 }
 
 // Cluster-Based Recusive Depth-First SingleThreaded Solver
-pub fn solve_11(map: MonolithMap) -> Vec<Tile> {
+pub fn solve_11(map: MonolithMap, options: &SolverOptions) -> Result<Vec<Tile>, MyError> {
     fn worker_thread(
         job_queue: Arc<ArrayQueue<(Tile, MonolithMap)>>,
         results: Arc<Mutex<Vec<SolvedPath>>>,
         current_best: Arc<AtomicU32>,
         exit_flag: Arc<AtomicBool>,
+        ranking: Ranking,
+        deadline: Deadline,
     ) {
         let mut local_results = Vec::with_capacity(100);
         loop {
-            if exit_flag.load(Ordering::Acquire) {
+            if exit_flag.load(Ordering::Acquire) || deadline.has_passed() {
                 return;
             }
 
@@ -879,20 +1041,22 @@ pub fn solve_11(map: MonolithMap) -> Vec<Tile> {
                 steps
             };
 
-            solve_map(&mut local_results, &mut local_best, steps, map);
+            solve_map(
+                &mut local_results,
+                &mut local_best,
+                steps,
+                map,
+                &ranking,
+                &deadline,
+            );
             if !local_results.is_empty() {
-                local_results.sort();
-                local_results.reverse();
-                let (best_count, best_steps) = local_results.pop().unwrap();
-                if best_count < current_best.load(Ordering::Acquire) {
-                    current_best.store(best_count, Ordering::Release);
-                    results.lock().unwrap().push((best_count, best_steps));
-                    println!("Current best result is: {} tiles remaining.", best_count);
-                    if best_count == 0 {
+                let best = ranking.best(local_results.split_off(0));
+                if !ranking.prunes(best.count, current_best.load(Ordering::Acquire)) {
+                    record_result(&results, best, &current_best, &ranking);
+                    if ranking.is_final(current_best.load(Ordering::Acquire)) {
                         exit_flag.store(true, Ordering::Release);
                     }
                 }
-                local_results.clear();
             }
         }
     }
@@ -901,12 +1065,16 @@ pub fn solve_11(map: MonolithMap) -> Vec<Tile> {
         current_best: &mut u32,
         steps: Vec<Tile>,
         map: MonolithMap,
+        ranking: &Ranking,
+        deadline: &Deadline,
     ) {
         if !map.has_any_group() {
-            let count = map.get_dead_tiles_count();
-            if count < *current_best {
-                *current_best = count;
-                results.push((count, steps));
+            let result = ranking.path(map.get_dead_tiles_count(), steps);
+            if !ranking.prunes(result.count, *current_best)
+                && ranking.is_better(&result, results.last())
+            {
+                *current_best = result.count;
+                results.push(result);
             }
         } else {
             let clusters = map.all_tile_clusters();
@@ -914,7 +1082,11 @@ pub fn solve_11(map: MonolithMap) -> Vec<Tile> {
                 let mut new_map = map.clone();
                 let mut new_steps = steps.clone();
                 for cluster in clusters {
+                    if deadline.has_passed() {
+                        return;
+                    }
                     let cluster_map = map.create_map_from_cluster(&cluster);
+                    let cluster_ranking = ranking.with_map(&cluster_map);
                     let mut cluster_results = Vec::with_capacity(100);
                     let mut cluster_best = cluster_map.get_all_tiles_count();
 
@@ -923,27 +1095,30 @@ pub fn solve_11(map: MonolithMap) -> Vec<Tile> {
                         &mut cluster_best,
                         Vec::with_capacity(50),
                         cluster_map,
+                        &cluster_ranking,
+                        deadline,
                     );
 
                     if !cluster_results.is_empty() {
-                        cluster_results.sort();
-                        cluster_results.reverse();
-                        let (_, best_cluster_steps) = cluster_results.pop().unwrap();
-                        for step in best_cluster_steps {
+                        let best_cluster = cluster_ranking.best(cluster_results);
+                        for step in best_cluster.steps {
                             new_map.click(step.0, step.1);
                             new_steps.push(step);
                         }
                     }
                 }
-                solve_map(results, current_best, new_steps, new_map);
+                solve_map(results, current_best, new_steps, new_map, ranking, deadline);
             } else {
                 for group in map.all_groups() {
+                    if deadline.has_passed() {
+                        return;
+                    }
                     let first_tile = group[0];
                     let mut new_map = map.clone();
                     new_map.click(first_tile.0, first_tile.1);
 
                     let count = new_map.get_dead_tiles_count();
-                    if count >= *current_best {
+                    if ranking.prunes(count, *current_best) {
                         continue;
                     }
 
@@ -952,11 +1127,11 @@ pub fn solve_11(map: MonolithMap) -> Vec<Tile> {
                         temp.push(first_tile);
                         temp
                     };
-                    solve_map(results, current_best, new_steps, new_map);
+                    solve_map(results, current_best, new_steps, new_map, ranking, deadline);
                 }
             }
         }
-    };
+    }
 
     let job_queue = Arc::new(ArrayQueue::new(100));
     let all_groups = map.all_groups();
@@ -968,12 +1143,14 @@ pub fn solve_11(map: MonolithMap) -> Vec<Tile> {
         new_map.click(first_tile.0, first_tile.1);
         job_queue
             .push((first_tile, new_map))
-            .expect("Failed to push a starting map.");
+            .map_err(|_| MyError::QueueOverflow)?;
     }
 
     let current_best = Arc::new(AtomicU32::new(22 * 11));
     let result = Arc::new(Mutex::new(Vec::with_capacity(100)));
     let exit_flag = Arc::new(AtomicBool::new(false));
+    let ranking = options.ranking(&map);
+    let deadline = Deadline::new(options.time_limit);
 
     let workers: Vec<_> = (0..8)
         .map(|_| {
@@ -981,18 +1158,22 @@ pub fn solve_11(map: MonolithMap) -> Vec<Tile> {
             let result_clone = result.clone();
             let best_clone = current_best.clone();
             let exit_flag_clone = exit_flag.clone();
-            thread::spawn(|| {
-                worker_thread(job_queue_clone, result_clone, best_clone, exit_flag_clone)
+            let ranking_clone = ranking.clone();
+            thread::spawn(move || {
+                worker_thread(
+                    job_queue_clone,
+                    result_clone,
+                    best_clone,
+                    exit_flag_clone,
+                    ranking_clone,
+                    deadline,
+                )
             })
         })
         .collect();
 
-    for worker in workers {
-        worker.join().expect("Failed to join on a thread handle.");
-    }
+    join_all(workers)?;
 
-    let mut result_vector = result.lock().unwrap();
-    result_vector.sort();
-    result_vector.reverse();
-    result_vector.pop().unwrap_or_default().1
+    let results = lock(&result).drain(..).collect();
+    Ok(ranking.best(results).steps)
 }
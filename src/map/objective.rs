@@ -0,0 +1,364 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use super::monolith_map::{MonolithMap, Tile};
+use super::score::ScoreRules;
+use super::target::TargetSpec;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Objective {
+    /// Fewer tiles left on the board is better.
+    RemainingTiles,
+    /// Fewer clicks is better.
+    Clicks,
+    /// Shorter total mouse travel between consecutive clicks (in tiles) is better.
+    MouseTravel,
+    /// Higher in-game score (see `ScoreRules`) is better.
+    Score,
+    /// Better match of the target pattern (see `TargetSpec`) is better.
+    Target,
+}
+
+impl FromStr for Objective {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tiles" => Ok(Self::RemainingTiles),
+            "clicks" => Ok(Self::Clicks),
+            "travel" => Ok(Self::MouseTravel),
+            "score" => Ok(Self::Score),
+            "target" => Ok(Self::Target),
+            _ => Err(format!("Unknown objective '{}'", s)),
+        }
+    }
+}
+
+/// Lexicographic list of objectives, most important first.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Objectives(pub Vec<Objective>);
+
+impl Default for Objectives {
+    fn default() -> Self {
+        Objectives(vec![Objective::RemainingTiles])
+    }
+}
+
+impl FromStr for Objectives {
+    type Err = String;
+
+    /// Comma-separated list, e.g. `tiles,clicks,travel`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let objectives = s
+            .split(',')
+            .map(|x| x.trim().parse::<Objective>())
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Objectives(objectives))
+    }
+}
+
+pub fn get_mouse_travel(steps: &[Tile]) -> f64 {
+    steps
+        .windows(2)
+        .map(|pair| {
+            let dx = pair[0].0 as f64 - pair[1].0 as f64;
+            let dy = pair[0].1 as f64 - pair[1].1 as f64;
+            (dx * dx + dy * dy).sqrt()
+        })
+        .sum()
+}
+
+/// Path found by a solver with the values which the objectives compare. Paths are made
+/// by `Ranking::path`, which computes every value once instead of on every comparison.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SolvedPath {
+    /// Tiles left after the path.
+    pub count: u32,
+    pub steps: Vec<Tile>,
+    /// Values of the objectives besides the tiles and the clicks, 0 if they aren't used.
+    travel: f64,
+    score: u32,
+    target_score: u32,
+}
+
+/// Compares solved paths by the configured objectives.
+/// Paths are scored from `map`, the board they were solved on.
+#[derive(Debug, Clone)]
+pub struct Ranking {
+    objectives: Vec<Objective>,
+    map: MonolithMap,
+    rules: ScoreRules,
+    target: Option<TargetSpec>,
+}
+
+impl Ranking {
+    pub fn new(objectives: &Objectives, map: &MonolithMap, rules: &ScoreRules) -> Ranking {
+        Ranking {
+            objectives: objectives.0.clone(),
+            map: map.clone(),
+            rules: rules.clone(),
+            target: None,
+        }
+    }
+
+    /// Without a target `Objective::Target` considers all paths equal.
+    pub fn with_target(self, target: Option<&TargetSpec>) -> Ranking {
+        Ranking {
+            target: target.cloned(),
+            ..self
+        }
+    }
+
+    fn get_target_score(&self, steps: &[Tile]) -> u32 {
+        match &self.target {
+            Some(target) => {
+                let mut map = self.map.clone();
+                for step in steps {
+                    map.click(step.0, step.1);
+                }
+                target.get_score(&map)
+            }
+            None => 0,
+        }
+    }
+
+    /// Same objectives and rules for paths solved on a different board, e.g. a cluster.
+    pub fn with_map(&self, map: &MonolithMap) -> Ranking {
+        Ranking {
+            map: map.clone(),
+            ..self.clone()
+        }
+    }
+
+    /// Path leaving `count` tiles, with the values of the objectives.
+    pub fn path(&self, count: u32, steps: Vec<Tile>) -> SolvedPath {
+        let mut path = SolvedPath {
+            count,
+            steps,
+            ..SolvedPath::default()
+        };
+        for objective in &self.objectives {
+            match objective {
+                Objective::MouseTravel => path.travel = get_mouse_travel(&path.steps),
+                Objective::Score => path.score = self.rules.get_score(&self.map, &path.steps),
+                Objective::Target => path.target_score = self.get_target_score(&path.steps),
+                Objective::RemainingTiles | Objective::Clicks => (),
+            }
+        }
+        path
+    }
+
+    /// `Less` means that `a` is the better path. Both paths have to be made by this
+    /// ranking.
+    pub fn compare(&self, a: &SolvedPath, b: &SolvedPath) -> Ordering {
+        for objective in &self.objectives {
+            let ordering = match objective {
+                Objective::RemainingTiles => a.count.cmp(&b.count),
+                Objective::Clicks => a.steps.len().cmp(&b.steps.len()),
+                Objective::MouseTravel => {
+                    a.travel.partial_cmp(&b.travel).unwrap_or(Ordering::Equal)
+                }
+                Objective::Score => b.score.cmp(&a.score),
+                Objective::Target => b.target_score.cmp(&a.target_score),
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    }
+
+    /// Value of the most important objective for `path`, lower is better: scores are
+    /// negated. Paths sorted by `compare` have non-decreasing costs.
+    pub fn get_primary_cost(&self, path: &SolvedPath) -> f64 {
+        match self.objectives.first() {
+            Some(Objective::RemainingTiles) => f64::from(path.count),
+            Some(Objective::Clicks) => path.steps.len() as f64,
+            Some(Objective::MouseTravel) => path.travel,
+            Some(Objective::Score) => -f64::from(path.score),
+            Some(Objective::Target) => -f64::from(path.target_score),
+            None => 0.0,
+        }
+    }
+
+    pub fn is_better(&self, candidate: &SolvedPath, current: Option<&SolvedPath>) -> bool {
+        match current {
+            Some(current) => self.compare(candidate, current) == Ordering::Less,
+            None => true,
+        }
+    }
+
+    /// Returns the first of the best paths, or an empty path if there are none.
+    pub fn best(&self, results: Vec<SolvedPath>) -> SolvedPath {
+        let mut best: Option<SolvedPath> = None;
+        for result in results {
+            if self.is_better(&result, best.as_ref()) {
+                best = Some(result);
+            }
+        }
+        best.unwrap_or_default()
+    }
+
+    /// Whether a branch which already has `dead_tiles` can be skipped when the best
+    /// path found so far leaves `best_count` tiles. Dead tiles only bound the result
+    /// when the tiles come first, with other objectives first nothing is skipped and
+    /// the search ends at `SolverOptions::time_limit`.
+    pub fn prunes(&self, dead_tiles: u32, best_count: u32) -> bool {
+        match self.objectives.first() {
+            Some(Objective::RemainingTiles) => {
+                dead_tiles > best_count || (dead_tiles == best_count && self.objectives.len() == 1)
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether a path leaving `count` tiles can't be improved on.
+    pub fn is_final(&self, count: u32) -> bool {
+        count == 0 && self.objectives == [Objective::RemainingTiles]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        get_mouse_travel, MonolithMap, Objective, Objectives, Ranking, ScoreRules, SolvedPath,
+        TargetSpec,
+    };
+    use std::cmp::Ordering;
+
+    fn get_ranking(objectives: &str) -> Ranking {
+        Ranking::new(
+            &objectives.parse().unwrap(),
+            &MonolithMap::default(),
+            &ScoreRules::default(),
+        )
+    }
+
+    #[test]
+    fn test_parse_objectives() {
+        let objectives = "tiles, clicks,travel,score,target".parse::<Objectives>().unwrap();
+        assert_eq!(
+            objectives.0,
+            vec![
+                Objective::RemainingTiles,
+                Objective::Clicks,
+                Objective::MouseTravel,
+                Objective::Score,
+                Objective::Target
+            ]
+        );
+        assert_eq!(Objectives::default(), "tiles".parse().unwrap());
+        assert!("tiles,speed".parse::<Objectives>().is_err());
+    }
+
+    #[test]
+    fn test_mouse_travel() {
+        assert_eq!(get_mouse_travel(&[]), 0.0);
+        assert_eq!(get_mouse_travel(&[(1, 1)]), 0.0);
+        assert_eq!(get_mouse_travel(&[(0, 0), (3, 4), (3, 0)]), 9.0);
+    }
+
+    #[test]
+    fn test_lexicographic_compare() {
+        let ranking = get_ranking("tiles,clicks,travel");
+        let near = ranking.path(2, vec![(0, 0), (1, 0)]);
+        let far = ranking.path(2, vec![(0, 0), (9, 0)]);
+        let long = ranking.path(2, vec![(0, 0), (1, 0), (2, 0)]);
+        let better = ranking.path(1, vec![(0, 0), (1, 0), (2, 0), (20, 10)]);
+
+        assert_eq!(ranking.compare(&near, &far), Ordering::Less);
+        assert_eq!(ranking.compare(&far, &long), Ordering::Less);
+        assert_eq!(ranking.compare(&better, &near), Ordering::Less);
+        assert_eq!(
+            ranking.best(vec![long.clone(), far, better.clone(), near]),
+            better
+        );
+
+        let clicks_first = get_ranking("clicks,tiles");
+        assert_eq!(clicks_first.compare(&long, &better), Ordering::Less);
+        assert_eq!(clicks_first.best(vec![]), SolvedPath::default());
+    }
+
+    #[test]
+    fn test_pruning() {
+        let tiles_only = get_ranking("tiles");
+        assert!(tiles_only.prunes(5, 5));
+        assert!(!tiles_only.prunes(4, 5));
+        assert!(tiles_only.is_final(0));
+
+        let with_tiebreak = get_ranking("tiles,clicks");
+        assert!(!with_tiebreak.prunes(5, 5));
+        assert!(with_tiebreak.prunes(6, 5));
+        assert!(!with_tiebreak.is_final(0));
+
+        let clicks_first = get_ranking("clicks");
+        assert!(!clicks_first.prunes(100, 5));
+    }
+
+    #[test]
+    fn test_score_is_maximised() {
+        let map = "
+            ......................
+            ......................
+            ......................
+            ......................
+            ......................
+            ......................
+            ......................
+            .......32.............
+            ........11............
+            ......................
+            ......................
+        "
+        .parse::<MonolithMap>()
+        .unwrap();
+        let ranking = Ranking::new(&"score".parse().unwrap(), &map, &ScoreRules::default());
+        let cleared = ranking.path(0, vec![(8, 8), (7, 7)]);
+        let partial = ranking.path(2, vec![(8, 8)]);
+        assert_eq!(ranking.compare(&cleared, &partial), Ordering::Less);
+        assert!(!ranking.prunes(100, 0));
+        assert!(!ranking.is_final(0));
+
+        // the same paths mean nothing on an empty board
+        let empty = ranking.with_map(&MonolithMap::default());
+        assert_eq!(
+            empty.compare(&empty.path(0, cleared.steps), &empty.path(2, partial.steps)),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn test_target_is_maximised() {
+        let mut map = MonolithMap::default();
+        map.set(0, 0, 1);
+        map.set(1, 0, 1);
+        map.set(3, 0, 2);
+        map.set(4, 0, 2);
+        let target = TargetSpec {
+            clear: vec![(3, 0)],
+            keep: vec![(0, 0)],
+            ..TargetSpec::default()
+        };
+        let ranking = Ranking::new(&"target,clicks".parse().unwrap(), &map, &ScoreRules::default())
+            .with_target(Some(&target));
+        let wrong = ranking.path(0, vec![(0, 0)]);
+        let right = ranking.path(2, vec![(3, 0)]);
+        let both = ranking.path(0, vec![(0, 0), (3, 0)]);
+        assert_eq!(ranking.compare(&right, &wrong), Ordering::Less);
+        assert_eq!(ranking.compare(&right, &both), Ordering::Less);
+        assert_eq!(
+            ranking.best(vec![wrong.clone(), both, right.clone()]),
+            right
+        );
+
+        let no_target = ranking.with_target(None);
+        assert_eq!(
+            no_target.compare(
+                &no_target.path(0, wrong.steps),
+                &no_target.path(0, right.steps)
+            ),
+            Ordering::Equal
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use serde::{Deserialize, Serialize};
+
+use super::methods::{SolverOptions, SolvingMethods};
+use super::monolith_map::{MonolithMap, Tile, MAX_X, MAX_Y};
+use crate::errors::MyError;
+
+/// Default number of clicks which may be missing between two recorded boards.
+pub const DEFAULT_MAX_CLICKS: usize = 3;
+
+/// Click found between two recorded boards.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PlayedClick {
+    pub tile: Tile,
+    /// Index of the first recorded board which shows the click.
+    pub board: usize,
+    /// Size of the group which the click removed.
+    pub group_size: usize,
+    /// Tiles left after the best continuation found before the click.
+    pub best_before: u32,
+    /// Tiles left after the best continuation found after the click.
+    pub best_after: u32,
+}
+
+impl PlayedClick {
+    /// Tiles which the click cost compared to the best continuation.
+    pub fn lost_tiles(&self) -> u32 {
+        self.best_after.saturating_sub(self.best_before)
+    }
+}
+
+/// Game reconstructed from a sequence of boards and compared with the solver.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub clicks: Vec<PlayedClick>,
+    /// Boards which can't be reached from the previous ones (misrecognised or from
+    /// another game), they are left out.
+    pub skipped: Vec<usize>,
+    /// Tiles left on the last board.
+    pub remaining_tiles: u32,
+    /// Path of the solver from the first board.
+    pub solver_path: Vec<Tile>,
+    /// Tiles left after the solver path.
+    pub solver_remaining_tiles: u32,
+}
+
+/// Clicks which turn `before` into `after`, at most `max_clicks` of them. Boards only
+/// lose tiles, and only groups which are empty on `after` can have been clicked.
+pub fn find_clicks(
+    before: &MonolithMap,
+    after: &MonolithMap,
+    max_clicks: usize,
+) -> Option<Vec<Tile>> {
+    if before == after {
+        return Some(vec![]);
+    }
+    if max_clicks == 0 {
+        return None;
+    }
+    for x in 0..MAX_X {
+        for y in 0..MAX_Y {
+            if before.get(x, y) == 0 && after.get(x, y) != 0 {
+                return None;
+            }
+        }
+    }
+
+    for group in before.all_groups() {
+        if group.iter().any(|tile| after.get(tile.0, tile.1) != 0) {
+            continue;
+        }
+        let tile = group[0];
+        let mut next = before.clone();
+        next.click(tile.0, tile.1);
+        if let Some(clicks) = find_clicks(&next, after, max_clicks - 1) {
+            let mut result = Vec::with_capacity(clicks.len() + 1);
+            result.push(tile);
+            result.extend(clicks);
+            return Some(result);
+        }
+    }
+    None
+}
+
+/// Tiles left after playing the steps on the board.
+fn get_remaining_tiles(map: &MonolithMap, steps: &[Tile]) -> u32 {
+    let mut map = map.clone();
+    for step in steps {
+        map.click(step.0, step.1);
+    }
+    map.get_all_tiles_count()
+}
+
+/// Reconstructs the clicks between the recorded boards and solves the board before
+/// every click with `method`, to show where tiles were lost.
+pub fn replay_game(
+    boards: &[MonolithMap],
+    method: &SolvingMethods,
+    options: &SolverOptions,
+    max_clicks: usize,
+) -> Result<Replay, MyError> {
+    let first = match boards.first() {
+        Some(first) => first,
+        None => {
+            return Ok(Replay {
+                clicks: vec![],
+                skipped: vec![],
+                remaining_tiles: 0,
+                solver_path: vec![],
+                solver_remaining_tiles: 0,
+            })
+        }
+    };
+    let best = |map: &MonolithMap| -> Result<(Vec<Tile>, u32), MyError> {
+        let steps = method.clone().solve_with(map.clone(), options)?;
+        let remaining_tiles = get_remaining_tiles(map, &steps);
+        Ok((steps, remaining_tiles))
+    };
+
+    let (solver_path, solver_remaining_tiles) = best(first)?;
+    let mut clicks = vec![];
+    let mut skipped = vec![];
+    let mut current = first.clone();
+    let mut best_current = solver_remaining_tiles;
+    for (index, board) in boards.iter().enumerate().skip(1) {
+        let tiles = match find_clicks(&current, board, max_clicks) {
+            Some(tiles) => tiles,
+            None => {
+                skipped.push(index);
+                continue;
+            }
+        };
+        for tile in tiles {
+            let group_size = current.get_group(tile.0, tile.1).len();
+            current.click(tile.0, tile.1);
+            let best_after = best(&current)?.1;
+            clicks.push(PlayedClick {
+                tile,
+                board: index,
+                group_size,
+                best_before: best_current,
+                best_after,
+            });
+            best_current = best_after;
+        }
+    }
+
+    Ok(Replay {
+        clicks,
+        skipped,
+        remaining_tiles: current.get_all_tiles_count(),
+        solver_path,
+        solver_remaining_tiles,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{find_clicks, replay_game};
+    use crate::map::{MonolithMap, SolverOptions, SolvingMethods, MAX_Y};
+
+    /// Board with tiles in the bottom three rows.
+    fn get_map() -> MonolithMap {
+        MonolithMap::from_fn(MAX_Y - 3..MAX_Y, |x, y| (x * 5 + y * 3 + x * y) % 4 + 1)
+    }
+
+    fn play(map: &MonolithMap, count: usize) -> (Vec<MonolithMap>, Vec<(usize, usize)>) {
+        let mut boards = vec![map.clone()];
+        let mut tiles = vec![];
+        let mut current = map.clone();
+        for _ in 0..count {
+            let tile = current.all_groups()[0][0];
+            current.click(tile.0, tile.1);
+            boards.push(current.clone());
+            tiles.push(tile);
+        }
+        (boards, tiles)
+    }
+
+    #[test]
+    fn test_find_clicks() {
+        let map = get_map();
+        let (boards, tiles) = play(&map, 3);
+        assert_eq!(find_clicks(&map, &map, 1), Some(vec![]));
+        assert_eq!(find_clicks(&map, &boards[1], 1), Some(vec![tiles[0]]));
+        assert_eq!(find_clicks(&map, &boards[3], 3), Some(tiles.clone()));
+        assert_eq!(find_clicks(&map, &boards[3], 2), None);
+        // boards never get tiles back
+        assert_eq!(find_clicks(&boards[1], &map, 3), None);
+    }
+
+    #[test]
+    fn test_replay_game() {
+        let map = get_map();
+        let (boards, tiles) = play(&map, 6);
+        // a missing frame, a repeated frame and a misrecognised one
+        let mut glitch = boards[4].clone();
+        glitch.set(0, MAX_Y - 1, 0);
+        let recorded = vec![
+            boards[0].clone(),
+            boards[1].clone(),
+            boards[1].clone(),
+            boards[3].clone(),
+            glitch,
+            boards[4].clone(),
+            boards[6].clone(),
+        ];
+        let method = SolvingMethods::Method10;
+        let replay = replay_game(&recorded, &method, &SolverOptions::default(), 3).unwrap();
+
+        let played = replay.clicks.iter().map(|x| x.tile).collect::<Vec<_>>();
+        assert_eq!(played, tiles);
+        assert_eq!(
+            replay.clicks.iter().map(|x| x.board).collect::<Vec<_>>(),
+            vec![1, 3, 3, 5, 6, 6]
+        );
+        assert_eq!(replay.skipped, vec![4]);
+        assert_eq!(replay.remaining_tiles, boards[6].get_all_tiles_count());
+
+        let solver_path = map.clone().solve(method).unwrap();
+        assert_eq!(replay.solver_path, solver_path);
+        assert_eq!(replay.clicks[0].best_before, replay.solver_remaining_tiles);
+        for pair in replay.clicks.windows(2) {
+            assert_eq!(pair[0].best_after, pair[1].best_before);
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+
+use super::monolith_map::{MonolithMap, Tile};
+use crate::errors::{MyError, WithPath};
+
+/// Scoring rules of the Monolith minigame.
+/// Defaults are an approximation of the game, load exact values from a file if you have them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScoreRules {
+    /// Points for every removed tile.
+    pub points_per_tile: u32,
+    /// Groups of at least this many tiles get a bonus.
+    pub bonus_group_size: usize,
+    /// Bonus points for every tile of a bonus group beyond `bonus_group_size - 1`.
+    pub bonus_per_tile: u32,
+    /// Bonus for leaving no tiles on the board.
+    pub clear_bonus: u32,
+    /// Time limit in seconds, clicks after it don't count.
+    pub time_limit: f64,
+    /// Time in seconds one click takes.
+    pub seconds_per_click: f64,
+}
+
+impl Default for ScoreRules {
+    fn default() -> Self {
+        ScoreRules {
+            points_per_tile: 10,
+            bonus_group_size: 5,
+            bonus_per_tile: 20,
+            clear_bonus: 5000,
+            time_limit: 300.0,
+            seconds_per_click: 1.5,
+        }
+    }
+}
+
+impl ScoreRules {
+    pub fn load(path: &Path) -> Result<ScoreRules, MyError> {
+        let mut file = std::fs::File::open(path).with_path(path)?;
+        let mut buffer = String::new();
+        file.read_to_string(&mut buffer).with_path(path)?;
+        serde_json::from_str::<ScoreRules>(&buffer).with_path(path)
+    }
+
+    /// Points for removing a group of `group_size` tiles.
+    pub fn get_group_score(&self, group_size: usize) -> u32 {
+        let mut score = self.points_per_tile * group_size as u32;
+        if group_size >= self.bonus_group_size {
+            let extra_tiles = group_size + 1 - self.bonus_group_size;
+            score += self.bonus_per_tile * extra_tiles as u32;
+        }
+        score
+    }
+
+    /// How many clicks fit into the time limit.
+    pub fn get_max_clicks(&self) -> usize {
+        if self.seconds_per_click <= 0.0 {
+            return usize::MAX;
+        }
+        (self.time_limit / self.seconds_per_click).floor() as usize
+    }
+
+    /// Score for playing `steps` on `map`. Clicks on tiles without a group score nothing.
+    pub fn get_score(&self, map: &MonolithMap, steps: &[Tile]) -> u32 {
+        let mut map = map.clone();
+        let mut score = 0;
+        for step in steps.iter().take(self.get_max_clicks()) {
+            let group = map.get_group(step.0, step.1);
+            if group.is_empty() {
+                continue;
+            }
+            score += self.get_group_score(group.len());
+            map.click(step.0, step.1);
+        }
+        if steps.len() <= self.get_max_clicks() && map.get_all_tiles_count() == 0 {
+            score += self.clear_bonus;
+        }
+        score
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MonolithMap, ScoreRules};
+
+    fn get_rules() -> ScoreRules {
+        ScoreRules {
+            points_per_tile: 1,
+            bonus_group_size: 3,
+            bonus_per_tile: 10,
+            clear_bonus: 100,
+            time_limit: 10.0,
+            seconds_per_click: 2.0,
+        }
+    }
+
+    #[test]
+    fn test_group_score() {
+        let rules = get_rules();
+        assert_eq!(rules.get_group_score(2), 2);
+        assert_eq!(rules.get_group_score(3), 13);
+        assert_eq!(rules.get_group_score(5), 35);
+        assert_eq!(rules.get_max_clicks(), 5);
+    }
+
+    #[test]
+    fn test_score_with_clear_bonus() {
+        let map = "
+            ......................
+            ......................
+            ......................
+            ......................
+            ......................
+            ......................
+            ......................
+            .......32.............
+            ........11............
+            ......................
+            ......................
+        "
+        .parse::<MonolithMap>()
+        .unwrap();
+        let rules = get_rules();
+
+        // first click advances (8, 7) to 3, second removes the 3-3 pair
+        assert_eq!(rules.get_score(&map, &[(8, 8), (7, 7)]), 2 + 2 + 100);
+        // clicking a single tile does nothing
+        assert_eq!(rules.get_score(&map, &[(7, 7), (8, 8)]), 2);
+
+        let slow_rules = ScoreRules {
+            time_limit: 3.0,
+            ..get_rules()
+        };
+        assert_eq!(slow_rules.get_score(&map, &[(8, 8), (7, 7)]), 2);
+    }
+}
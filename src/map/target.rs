@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+
+use super::monolith_map::{MonolithMap, Tile, MAX_X, MAX_Y};
+use crate::errors::{MyError, WithPath};
+
+/// Pattern the board should end up in.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct TargetSpec {
+    /// Cells which should be empty at the end.
+    #[serde(default)]
+    pub clear: Vec<Tile>,
+    /// Cells which should still have a tile at the end.
+    #[serde(default)]
+    pub keep: Vec<Tile>,
+    /// Optional `[x, y, weight]` entries, cells which aren't listed weigh 1.
+    #[serde(default)]
+    pub weights: Vec<(usize, usize, u32)>,
+}
+
+impl TargetSpec {
+    /// Loads a spec from JSON. A plain list of cells (the old `tiles.txt` format)
+    /// is read as cells to clear.
+    pub fn load(path: &Path) -> Result<TargetSpec, MyError> {
+        let mut file = std::fs::File::open(path).with_path(path)?;
+        let mut buffer = String::new();
+        file.read_to_string(&mut buffer).with_path(path)?;
+        let spec = match serde_json::from_str::<Vec<Tile>>(&buffer) {
+            Ok(clear) => TargetSpec {
+                clear,
+                ..TargetSpec::default()
+            },
+            Err(_) => serde_json::from_str::<TargetSpec>(&buffer).with_path(path)?,
+        };
+        spec.validated()
+    }
+
+    /// Removes duplicate cells and checks that every cell is on the board and
+    /// isn't both cleared and kept.
+    pub fn validated(mut self) -> Result<TargetSpec, MyError> {
+        self.clear.sort_unstable();
+        self.clear.dedup();
+        self.keep.sort_unstable();
+        self.keep.dedup();
+
+        let weighted = self.weights.iter().map(|x| (x.0, x.1));
+        for tile in self
+            .clear
+            .iter()
+            .chain(self.keep.iter())
+            .cloned()
+            .chain(weighted)
+        {
+            if tile.0 >= MAX_X || tile.1 >= MAX_Y {
+                return Err(MyError::InvalidTarget(format!(
+                    "cell {:?} is outside of the board",
+                    tile
+                )));
+            }
+        }
+        if let Some(tile) = self.clear.iter().find(|x| self.keep.contains(x)) {
+            return Err(MyError::InvalidTarget(format!(
+                "cell {:?} has to be both cleared and kept",
+                tile
+            )));
+        }
+        Ok(self)
+    }
+
+    fn get_weight(&self, tile: &Tile) -> u32 {
+        self.weights
+            .iter()
+            .find(|x| x.0 == tile.0 && x.1 == tile.1)
+            .map_or(1, |x| x.2)
+    }
+
+    /// Total weight of the cells which match the target, higher is better.
+    pub fn get_score(&self, map: &MonolithMap) -> u32 {
+        let cleared = self.clear.iter().filter(|x| map.get(x.0, x.1) == 0);
+        let kept = self.keep.iter().filter(|x| map.get(x.0, x.1) != 0);
+        cleared.chain(kept).map(|x| self.get_weight(x)).sum()
+    }
+
+    /// Score of a board which fully matches the target.
+    pub fn get_max_score(&self) -> u32 {
+        self.clear
+            .iter()
+            .chain(self.keep.iter())
+            .map(|x| self.get_weight(x))
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MonolithMap, TargetSpec};
+
+    #[test]
+    fn test_target_score() {
+        let map = "
+            ......................
+            ......................
+            ......................
+            ......................
+            ......................
+            ......................
+            ......................
+            .......32.............
+            ........11............
+            ......................
+            ......................
+        "
+        .parse::<MonolithMap>()
+        .unwrap();
+        let target = TargetSpec {
+            clear: vec![(8, 8), (9, 8), (0, 0)],
+            keep: vec![(7, 7), (8, 7)],
+            weights: vec![(7, 7, 5)],
+        }
+        .validated()
+        .unwrap();
+
+        assert_eq!(target.get_max_score(), 9);
+        assert_eq!(target.get_score(&map), 1 + 5 + 1);
+
+        let mut map = map;
+        map.click(8, 8);
+        assert_eq!(target.get_score(&map), 3 + 5 + 1);
+        map.click(7, 7);
+        assert_eq!(target.get_score(&map), 3);
+    }
+
+    #[test]
+    fn test_invalid_target() {
+        let outside = TargetSpec {
+            clear: vec![(22, 0)],
+            ..TargetSpec::default()
+        };
+        assert!(outside.validated().is_err());
+
+        let conflict = TargetSpec {
+            clear: vec![(1, 1)],
+            keep: vec![(1, 1)],
+            ..TargetSpec::default()
+        };
+        assert!(conflict.validated().is_err());
+    }
+
+    #[test]
+    fn test_load_target() {
+        let directory = std::env::temp_dir().join(format!(
+            "monolith_solver_test_load_target_{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&directory).unwrap();
+        let plain = directory.join("tiles.txt");
+        std::fs::write(&plain, "[[3, 1], [2, 0], [3, 1]]").unwrap();
+        let full = directory.join("target.json");
+        std::fs::write(&full, r#"{"clear": [[2, 0]], "keep": [[5, 5]]}"#).unwrap();
+        let broken = directory.join("broken.json");
+        std::fs::write(&broken, "[[3, 1], [2").unwrap();
+
+        let plain = TargetSpec::load(&plain);
+        let full = TargetSpec::load(&full);
+        let broken = TargetSpec::load(&broken);
+        let missing = TargetSpec::load(&directory.join("missing.json"));
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(plain.unwrap().clear, vec![(2, 0), (3, 1)]);
+        assert_eq!(full.unwrap().keep, vec![(5, 5)]);
+        assert!(broken.is_err());
+        assert!(missing.is_err());
+    }
+}
@@ -0,0 +1,274 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::monolith_map::{MonolithMap, MAX_TILE, MAX_X, MAX_Y};
+use crate::errors::MyError;
+
+/// Starts a comment at the start of a line or after a space in a row, which goes to
+/// the end of the line.
+const COMMENT: char = '#';
+/// Cell without a tile.
+const EMPTY: char = '.';
+
+/// How `.map` files are written. Both formats are read, see `MapFormat::detect`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapFormat {
+    /// JSON object with the metadata, see `MapFile`. Older versions wrote plain rows of
+    /// numbers.
+    Json,
+    /// Rows of characters, see `TextMap`.
+    Text,
+}
+
+impl FromStr for MapFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "text" => Ok(Self::Text),
+            _ => Err(format!("Unknown map format '{}'", s)),
+        }
+    }
+}
+
+impl MapFormat {
+    /// Format of the file content: JSON starts with a bracket, which can't start a
+    /// line of the text format.
+    pub fn detect(content: &str) -> MapFormat {
+        match content.trim_start().chars().next() {
+            Some('[') | Some('{') => MapFormat::Json,
+            _ => MapFormat::Text,
+        }
+    }
+}
+
+/// Board in the text format, which is easy to fix by hand:
+///
+/// ```text
+/// # comments start with '#'
+/// difficulty: mean
+/// ......................
+/// ..12..................
+/// ...
+/// ```
+///
+/// `key: value` lines before the board are its header, values can contain `#`. Then
+/// every row is a line of 22 characters: `.` (or `0`) for a cleared cell, `1` to `4` or
+/// `a` to `d` for the tile types. Empty lines and spaces within the rows are ignored.
+/// Rows can end with a comment after a space.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TextMap {
+    pub header: Vec<(String, String)>,
+    pub map: MonolithMap,
+}
+
+fn parse_tile(c: char) -> Option<u8> {
+    let tile = match c {
+        EMPTY => 0,
+        '0'..='9' => c as u8 - b'0',
+        'a'..='z' => c as u8 - b'a' + 1,
+        'A'..='Z' => c as u8 - b'A' + 1,
+        _ => return None,
+    };
+    Some(tile).filter(|tile| *tile <= MAX_TILE)
+}
+
+/// Line up to a comment which starts after a space.
+fn strip_comment(line: &str) -> &str {
+    let mut previous = None;
+    for (index, c) in line.char_indices() {
+        if c == COMMENT && matches!(previous, Some(x) if char::is_whitespace(x)) {
+            return &line[..index];
+        }
+        previous = Some(c);
+    }
+    line
+}
+
+impl FromStr for TextMap {
+    type Err = MyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut header = vec![];
+        let mut rows = vec![];
+        for line in s.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(COMMENT) {
+                continue;
+            }
+            let row = strip_comment(line);
+            match line.find(':') {
+                Some(index) if rows.is_empty() && row.contains(':') => header.push((
+                    line[..index].trim().to_string(),
+                    line[index + 1..].trim().to_string(),
+                )),
+                _ => rows.push(
+                    row.chars()
+                        .filter(|c| !c.is_whitespace())
+                        .collect::<Vec<_>>(),
+                ),
+            }
+        }
+
+        if rows.len() != MAX_Y {
+            return Err(MyError::InvalidMapSize(rows.len()));
+        }
+        let mut map = MonolithMap::default();
+        for (y, row) in rows.iter().enumerate() {
+            if row.len() != MAX_X {
+                return Err(MyError::InvalidRowLength {
+                    row: y,
+                    columns: row.len(),
+                });
+            }
+            for (x, c) in row.iter().enumerate() {
+                match parse_tile(*c) {
+                    Some(tile) => map.set(x, y, tile),
+                    None => {
+                        return Err(MyError::InvalidTile {
+                            x,
+                            y,
+                            value: format!("'{}'", c),
+                        })
+                    }
+                }
+            }
+        }
+        Ok(TextMap { header, map })
+    }
+}
+
+impl fmt::Display for TextMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (key, value) in &self.header {
+            writeln!(f, "{}: {}", key, value)?;
+        }
+        write!(f, "{}", self.map)
+    }
+}
+
+/// Board in the text format, the header is ignored.
+impl FromStr for MonolithMap {
+    type Err = MyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(s.parse::<TextMap>()?.map)
+    }
+}
+
+/// Board in the text format, without a header.
+impl fmt::Display for MonolithMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for y in 0..MAX_Y {
+            let row = (0..MAX_X)
+                .map(|x| match self.get(x, y) {
+                    0 => EMPTY,
+                    tile => (b'0' + tile) as char,
+                })
+                .collect::<String>();
+            writeln!(f, "{}", row)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MapFormat, TextMap};
+    use crate::errors::MyError;
+    use crate::map::{MonolithMap, MAX_X, MAX_Y};
+
+    fn get_map() -> MonolithMap {
+        MonolithMap::from_fn(0..MAX_Y, |x, y| (x * 3 + y * y) % 5)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let map = get_map();
+        let text = map.to_string();
+        assert_eq!(text.lines().count(), MAX_Y);
+        assert!(text.lines().all(|x| x.len() == MAX_X));
+        assert_eq!(text.parse::<MonolithMap>().unwrap(), map);
+
+        let text_map = TextMap {
+            header: vec![("difficulty".to_string(), "Mean".to_string())],
+            map,
+        };
+        assert_eq!(text_map.to_string().parse::<TextMap>().unwrap(), text_map);
+    }
+
+    #[test]
+    fn test_parse() {
+        let text = "
+            # fixed by hand
+            difficulty: mean
+            source: capture #3.png
+
+            ......................
+            ......................
+            ......................
+            ......................
+            ......................
+            ......................
+            ......................   # 6
+            ..........ab..........
+            .......... 34 ..........
+            ..........00..........
+            ..........1D.......... # 11: last row
+        ";
+        let text_map = text.parse::<TextMap>().unwrap();
+        assert_eq!(
+            text_map.header,
+            vec![
+                ("difficulty".to_string(), "mean".to_string()),
+                ("source".to_string(), "capture #3.png".to_string())
+            ]
+        );
+        let map = text_map.map;
+        assert_eq!(map.get_all_tiles_count(), 6);
+        assert_eq!(map.get(10, 7), 1);
+        assert_eq!(map.get(11, 7), 2);
+        assert_eq!(map.get(10, 8), 3);
+        assert_eq!(map.get(11, 8), 4);
+        assert_eq!(map.get(10, 9), 0);
+        assert_eq!(map.get(11, 10), 4);
+
+        let error = text.replace("ab", "ax").parse::<MonolithMap>().unwrap_err();
+        assert!(matches!(error, MyError::InvalidTile { x: 11, y: 7, ref value } if value == "'x'"));
+        let error = text.replace("1D", "5D").parse::<MonolithMap>().unwrap_err();
+        assert!(matches!(error, MyError::InvalidTile { x: 10, y: 10, .. }));
+        let error = text
+            .replace("00.", "00")
+            .parse::<MonolithMap>()
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            MyError::InvalidRowLength {
+                row: 9,
+                columns: 21
+            }
+        ));
+        let error = text
+            .replace("# 6", "\n1")
+            .parse::<MonolithMap>()
+            .unwrap_err();
+        assert!(matches!(error, MyError::InvalidMapSize(12)));
+        let error = text
+            .replace("   # 6", "#6")
+            .parse::<MonolithMap>()
+            .unwrap_err();
+        assert!(matches!(error, MyError::InvalidRowLength { row: 6, .. }));
+    }
+
+    #[test]
+    fn test_detect_format() {
+        let map = get_map();
+        let json = serde_json::to_string(&map).unwrap();
+        assert_eq!(MapFormat::detect(&json), MapFormat::Json);
+        assert_eq!(MapFormat::detect(&format!("\n  {}", json)), MapFormat::Json);
+        assert_eq!(MapFormat::detect(&map.to_string()), MapFormat::Text);
+        assert_eq!("Text".parse::<MapFormat>(), Ok(MapFormat::Text));
+        assert!("yaml".parse::<MapFormat>().is_err());
+    }
+}
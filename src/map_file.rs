@@ -0,0 +1,341 @@
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::io::Read;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::difficulty::Difficulty;
+use crate::errors::{MyError, WithPath};
+use crate::map::{MapFormat, MonolithMap, TextMap, MAX_X, MAX_Y};
+use crate::recognize::RecognitionResult;
+
+/// Schema version of the `.map` files written by this program. Version 0 is a plain
+/// board without metadata, as written by older versions.
+pub const MAP_FILE_VERSION: u32 = 1;
+
+/// Where a board comes from, every value is optional.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct MapMetadata {
+    /// Difficulty detected from the tile colors.
+    pub difficulty: Option<Difficulty>,
+    /// Screenshot or recording the board was recognised from.
+    pub source: Option<String>,
+    /// Hash of the screenshot file, see `hash_source`.
+    pub source_hash: Option<String>,
+    /// Lowest confidence of a recognised tile, from 0 to 1.
+    pub confidence: Option<f64>,
+    /// Calibration file of the tile colors, `None` for the built-in colors.
+    pub calibration: Option<String>,
+    /// Seconds since 1970-01-01 UTC when the board was recognised.
+    pub created: Option<u64>,
+    /// Name and version of the program which recognised the board.
+    pub generator: Option<String>,
+    /// Hash of the board when it was written with tiles which couldn't be recognised
+    /// reliably, see `MapFile::check_verified`.
+    pub unverified: Option<String>,
+}
+
+/// FNV-1a hash of the screenshot file as 16 hex digits, to tell which screenshot a
+/// board belongs to.
+pub fn hash_source(data: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in data {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+impl MapMetadata {
+    /// Metadata of a board recognised now by this program, without the source.
+    pub fn from_recognition(recognition: &RecognitionResult) -> MapMetadata {
+        let confidence = recognition
+            .confidence
+            .iter()
+            .flatten()
+            .cloned()
+            .fold(f64::INFINITY, f64::min);
+        MapMetadata {
+            difficulty: Some(recognition.difficulty),
+            confidence: Some(confidence).filter(|x| x.is_finite()),
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|x| x.as_secs()),
+            generator: Some(format!(
+                "{} {}",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            )),
+            ..MapMetadata::default()
+        }
+    }
+}
+
+/// Contents of a `.map` file: the board with its size, schema version and metadata.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MapFile {
+    pub version: u32,
+    pub columns: usize,
+    pub rows: usize,
+    #[serde(flatten)]
+    pub metadata: MapMetadata,
+    pub tiles: MonolithMap,
+}
+
+fn hash_tiles(tiles: &MonolithMap) -> String {
+    hash_source(tiles.to_string().as_bytes())
+}
+
+impl MapFile {
+    pub fn new(tiles: MonolithMap, metadata: MapMetadata) -> MapFile {
+        MapFile {
+            version: MAP_FILE_VERSION,
+            columns: MAX_X,
+            rows: MAX_Y,
+            metadata,
+            tiles,
+        }
+    }
+
+    /// Marks the board as guessed, it isn't used until the tiles are changed or the
+    /// mark is removed by hand.
+    pub fn mark_unverified(&mut self) {
+        self.metadata.unverified = Some(hash_tiles(&self.tiles));
+    }
+
+    /// Refuses boards which are still marked as guessed and weren't edited since.
+    pub fn check_verified(&self) -> Result<(), MyError> {
+        match &self.metadata.unverified {
+            Some(hash) if *hash == hash_tiles(&self.tiles) => Err(MyError::UnverifiedMap),
+            _ => Ok(()),
+        }
+    }
+
+    /// Refuses files of newer versions and of other board sizes.
+    fn validated(self) -> Result<MapFile, MyError> {
+        if self.version > MAP_FILE_VERSION {
+            return Err(MyError::UnsupportedMapVersion(self.version));
+        }
+        if self.columns != MAX_X || self.rows != MAX_Y {
+            return Err(MyError::InvalidMapDimensions {
+                columns: self.columns,
+                rows: self.rows,
+            });
+        }
+        Ok(self)
+    }
+
+    /// Reads a `.map` file in any format: a JSON object with metadata, a plain JSON
+    /// board (version 0) or text.
+    pub fn from_content(content: &str) -> Result<MapFile, MyError> {
+        if MapFormat::detect(content) == MapFormat::Text {
+            return MapFile::from_text(content.parse()?);
+        }
+        if content.trim_start().starts_with('[') {
+            let rows = serde_json::from_str::<Vec<Vec<serde_json::Value>>>(content)?;
+            let mut map_file = MapFile::new(MonolithMap::try_from(rows)?, MapMetadata::default());
+            map_file.version = 0;
+            return Ok(map_file);
+        }
+        serde_json::from_str::<MapFile>(content)?.validated()
+    }
+
+    pub fn load(path: &Path) -> Result<MapFile, MyError> {
+        let mut file = std::fs::File::open(path).with_path(path)?;
+        let mut buffer = String::new();
+        file.read_to_string(&mut buffer).with_path(path)?;
+        MapFile::from_content(&buffer).with_path(path)
+    }
+
+    pub fn save(&self, path: &Path, format: MapFormat) -> Result<(), MyError> {
+        match format {
+            MapFormat::Json => {
+                let file = std::fs::File::create(path).with_path(path)?;
+                serde_json::to_writer(file, self).with_path(path)
+            }
+            MapFormat::Text => std::fs::write(path, self.to_text().to_string()).with_path(path),
+        }
+    }
+
+    /// Board in the text format, with the version and the metadata in the header.
+    pub fn to_text(&self) -> TextMap {
+        let metadata = &self.metadata;
+        let values = vec![
+            ("version", Some(self.version.to_string())),
+            (
+                "difficulty",
+                metadata.difficulty.map(|x| format!("{:?}", x)),
+            ),
+            ("source", metadata.source.clone()),
+            ("source_hash", metadata.source_hash.clone()),
+            ("confidence", metadata.confidence.map(|x| x.to_string())),
+            ("calibration", metadata.calibration.clone()),
+            ("created", metadata.created.map(|x| x.to_string())),
+            ("generator", metadata.generator.clone()),
+            ("unverified", metadata.unverified.clone()),
+        ];
+        TextMap {
+            header: values
+                .into_iter()
+                .filter_map(|(key, value)| value.map(|value| (key.to_string(), value)))
+                .collect(),
+            map: self.tiles.clone(),
+        }
+    }
+
+    /// Board from the text format, a header without a version is version 0. Unknown
+    /// keys are ignored, so the header can hold notes.
+    pub fn from_text(text: TextMap) -> Result<MapFile, MyError> {
+        let mut map_file = MapFile::new(text.map, MapMetadata::default());
+        map_file.version = 0;
+        for (key, value) in text.header {
+            let invalid = || MyError::InvalidInput(format!("invalid {} '{}'", key, value));
+            let metadata = &mut map_file.metadata;
+            match key.as_str() {
+                "version" => map_file.version = value.parse().map_err(|_| invalid())?,
+                "difficulty" => metadata.difficulty = Some(value.parse().map_err(|_| invalid())?),
+                "source" => metadata.source = Some(value),
+                "source_hash" => metadata.source_hash = Some(value),
+                "confidence" => metadata.confidence = Some(value.parse().map_err(|_| invalid())?),
+                "calibration" => metadata.calibration = Some(value),
+                "created" => metadata.created = Some(value.parse().map_err(|_| invalid())?),
+                "generator" => metadata.generator = Some(value),
+                "unverified" => metadata.unverified = Some(value),
+                _ => (),
+            }
+        }
+        map_file.validated()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{hash_source, MapFile, MapMetadata, MAP_FILE_VERSION};
+    use crate::difficulty::Difficulty;
+    use crate::errors::MyError;
+    use crate::map::{MapFormat, MonolithMap, MAX_Y};
+
+    fn get_map_file() -> MapFile {
+        let map = MonolithMap::from_fn(0..MAX_Y, |x, y| (x * 3 + y * y) % 5);
+        let metadata = MapMetadata {
+            difficulty: Some(Difficulty::Mean),
+            source: Some("board.png".to_string()),
+            source_hash: Some(hash_source(b"board")),
+            confidence: Some(0.75),
+            calibration: Some("calibration.json".to_string()),
+            created: Some(1_600_000_000),
+            generator: Some("monolith_solver 0.1.0".to_string()),
+            unverified: Some(hash_source(b"tiles")),
+        };
+        MapFile::new(map, metadata)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let map_file = get_map_file();
+        let json = serde_json::to_string(&map_file).unwrap();
+        assert_eq!(MapFile::from_content(&json).unwrap(), map_file);
+        let text = map_file.to_text().to_string();
+        assert_eq!(MapFile::from_content(&text).unwrap(), map_file);
+    }
+
+    #[test]
+    fn test_old_files() {
+        let map_file = get_map_file();
+        let json = serde_json::to_string(&map_file.tiles).unwrap();
+        let old = MapFile::from_content(&json).unwrap();
+        assert_eq!(old.version, 0);
+        assert_eq!(old.metadata, MapMetadata::default());
+        assert_eq!(old.tiles, map_file.tiles);
+
+        let old = MapFile::from_content(&map_file.tiles.to_string()).unwrap();
+        assert_eq!(old.version, 0);
+        assert_eq!(old.tiles, map_file.tiles);
+
+        // metadata is optional
+        let json = format!(
+            r#"{{"version": 1, "columns": 22, "rows": 11, "tiles": {}}}"#,
+            json
+        );
+        assert_eq!(
+            MapFile::from_content(&json).unwrap(),
+            MapFile::new(map_file.tiles, MapMetadata::default())
+        );
+    }
+
+    #[test]
+    fn test_invalid_files() {
+        let mut map_file = get_map_file();
+        map_file.version = MAP_FILE_VERSION + 1;
+        let json = serde_json::to_string(&map_file).unwrap();
+        assert!(matches!(
+            MapFile::from_content(&json),
+            Err(MyError::UnsupportedMapVersion(_))
+        ));
+
+        let mut map_file = get_map_file();
+        map_file.columns = 20;
+        let json = serde_json::to_string(&map_file).unwrap();
+        assert!(matches!(
+            MapFile::from_content(&json),
+            Err(MyError::InvalidMapDimensions {
+                columns: 20,
+                rows: 11
+            })
+        ));
+
+        let text = get_map_file().to_text().to_string();
+        let text = text.replace("confidence: 0.75", "confidence: high");
+        assert!(MapFile::from_content(&text).is_err());
+    }
+
+    #[test]
+    fn test_unverified() {
+        let mut map_file = MapFile::new(get_map_file().tiles, MapMetadata::default());
+        assert!(map_file.check_verified().is_ok());
+        map_file.mark_unverified();
+        assert!(matches!(
+            map_file.check_verified(),
+            Err(MyError::UnverifiedMap)
+        ));
+        let text = map_file.to_text().to_string();
+        assert!(MapFile::from_content(&text)
+            .unwrap()
+            .check_verified()
+            .is_err());
+
+        // fixed by hand
+        map_file.tiles.set(0, 0, 4 - map_file.tiles.get(0, 0));
+        assert!(map_file.check_verified().is_ok());
+        // or checked and the mark removed
+        let lines = text.lines().filter(|x| !x.starts_with("unverified"));
+        let text = lines.collect::<Vec<_>>().join("\n");
+        assert!(MapFile::from_content(&text)
+            .unwrap()
+            .check_verified()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let directory = std::env::temp_dir().join(format!(
+            "monolith_solver_test_map_file_{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&directory).unwrap();
+        let map_file = get_map_file();
+        let json = directory.join("json.map");
+        let text = directory.join("text.map");
+        map_file.save(&json, MapFormat::Json).unwrap();
+        map_file.save(&text, MapFormat::Text).unwrap();
+
+        let json = MapFile::load(&json);
+        let text = MonolithMap::load(&text);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(json.unwrap(), map_file);
+        assert_eq!(text.unwrap(), map_file.tiles);
+    }
+}
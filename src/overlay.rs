@@ -0,0 +1,172 @@
+use image::{DynamicImage, Rgb, RgbImage};
+
+use crate::cluster::TileRecognition;
+use crate::geometry::GridGeometry;
+use crate::map::Tile;
+
+const OUTLINE_COLOR: Rgb<u8> = Rgb([255, 255, 255]);
+const EMPTY_COLOR: Rgb<u8> = Rgb([128, 128, 128]);
+const AMBIGUOUS_COLOR: Rgb<u8> = Rgb([255, 0, 0]);
+const TEXT_COLOR: Rgb<u8> = Rgb([255, 255, 255]);
+const TEXT_BACKGROUND: Rgb<u8> = Rgb([0, 0, 0]);
+
+/// 3x5 pixel digits, every row is 3 bits with the leftmost pixel in the highest bit.
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+fn put_pixel(image: &mut RgbImage, x: u32, y: u32, color: Rgb<u8>) {
+    if x < image.width() && y < image.height() {
+        image.put_pixel(x, y, color);
+    }
+}
+
+fn draw_rect(image: &mut RgbImage, rect: (u32, u32, u32, u32), thickness: u32, color: Rgb<u8>) {
+    let (x, y, width, height) = rect;
+    for offset in 0..thickness.min(width / 2).min(height / 2) {
+        for dx in x + offset..x + width - offset {
+            put_pixel(image, dx, y + offset, color);
+            put_pixel(image, dx, y + height - 1 - offset, color);
+        }
+        for dy in y + offset..y + height - offset {
+            put_pixel(image, x + offset, dy, color);
+            put_pixel(image, x + width - 1 - offset, dy, color);
+        }
+    }
+}
+
+/// Draws the number with its top left corner at (x, y), on a dark background.
+fn draw_number(image: &mut RgbImage, x: u32, y: u32, scale: u32, number: u32) {
+    let text = number.to_string();
+    let width = (text.len() as u32 * 4 + 1) * scale;
+    for dy in 0..7 * scale {
+        for dx in 0..width {
+            put_pixel(image, x + dx, y + dy, TEXT_BACKGROUND);
+        }
+    }
+    for (index, digit) in text.bytes().enumerate() {
+        let glyph = DIGITS[usize::from(digit - b'0')];
+        let left = x + (index as u32 * 4 + 1) * scale;
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) == 0 {
+                    continue;
+                }
+                for sy in 0..scale {
+                    for sx in 0..scale {
+                        put_pixel(
+                            image,
+                            left + column * scale + sx,
+                            y + (row as u32 + 1) * scale + sy,
+                            TEXT_COLOR,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Copy of the screenshot with the sampled area of every tile outlined and labelled with
+/// the recognised type (top) and the confidence in percent (bottom). Tiles with a confidence
+/// below `min_confidence` are outlined in red, empty cells in grey.
+pub fn draw_recognition(
+    image: &DynamicImage,
+    geometry: &GridGeometry,
+    tiles: &[(Tile, TileRecognition)],
+    min_confidence: f64,
+) -> RgbImage {
+    let mut result = image.to_rgb();
+    let scale = ((geometry.tile_size / 40.0) as u32).max(1);
+    for (tile, recognition) in tiles {
+        let rect = geometry.get_tile_rect(tile.0 as u32, tile.1 as u32);
+        let (color, thickness) = if recognition.confidence < min_confidence {
+            (AMBIGUOUS_COLOR, 2 * scale)
+        } else if recognition.tile_type == 0 {
+            (EMPTY_COLOR, scale)
+        } else {
+            (OUTLINE_COLOR, scale)
+        };
+        draw_rect(&mut result, rect, thickness, color);
+
+        let left = rect.0 + thickness;
+        draw_number(
+            &mut result,
+            left,
+            rect.1 + thickness,
+            scale,
+            u32::from(recognition.tile_type),
+        );
+        let confidence = (recognition.confidence * 100.0).round() as u32;
+        draw_number(
+            &mut result,
+            left,
+            (rect.1 + rect.3).saturating_sub(thickness + 7 * scale),
+            scale,
+            confidence,
+        );
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::{draw_recognition, AMBIGUOUS_COLOR, OUTLINE_COLOR, TEXT_COLOR};
+    use crate::cluster::TileRecognition;
+    use crate::geometry::GridGeometry;
+    use image::{DynamicImage, Rgb};
+
+    #[test]
+    fn test_draw_recognition() {
+        let geometry = GridGeometry {
+            origin_x: 10.0,
+            origin_y: 10.0,
+            tile_size: 40.0,
+            border_width: 2.0,
+            columns: 2,
+            rows: 1,
+        };
+        let image = DynamicImage::new_rgb8(100, 60);
+        let tiles = vec![
+            (
+                (0, 0),
+                TileRecognition {
+                    tile_type: 1,
+                    confidence: 0.9,
+                },
+            ),
+            (
+                (1, 0),
+                TileRecognition {
+                    tile_type: 0,
+                    confidence: 0.1,
+                },
+            ),
+        ];
+        let result = draw_recognition(&image, &geometry, &tiles, 0.5);
+        assert_eq!(result.dimensions(), (100, 60));
+
+        let (x, y, width, height) = geometry.get_tile_rect(0, 0);
+        assert_eq!(*result.get_pixel(x + width / 2, y), OUTLINE_COLOR);
+        assert_eq!(
+            *result.get_pixel(x + width - 1, y + height / 2),
+            OUTLINE_COLOR
+        );
+        // top row of the digit 1 has only the middle pixel set
+        assert_eq!(*result.get_pixel(x + 1 + 1, y + 1 + 1), Rgb([0, 0, 0]));
+        assert_eq!(*result.get_pixel(x + 1 + 2, y + 1 + 1), TEXT_COLOR);
+
+        let (x, y, width, _) = geometry.get_tile_rect(1, 0);
+        assert_eq!(*result.get_pixel(x + width / 2, y), AMBIGUOUS_COLOR);
+        assert_eq!(*result.get_pixel(x + width / 2, y + 1), AMBIGUOUS_COLOR);
+    }
+}
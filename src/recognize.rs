@@ -0,0 +1,319 @@
+use image::{DynamicImage, GenericImageView};
+use serde::{Deserialize, Serialize};
+
+use crate::calibration::ColorModel;
+use crate::cluster::{TileClassifier, TileRecognition};
+use crate::difficulty::Difficulty;
+use crate::errors::MyError;
+use crate::geometry::{GeometrySource, GridGeometry};
+use crate::map::{MonolithMap, Tile, MAX_X, MAX_Y};
+use crate::sample::{sample_tile, TileSample};
+
+/// Tiles recognised with a lower confidence are ambiguous by default.
+pub const DEFAULT_MIN_CONFIDENCE: f64 = 0.2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecognitionOptions {
+    pub classifier: TileClassifier,
+    /// Tiles recognised with a lower confidence (from 0 to 1) are ambiguous.
+    pub min_confidence: f64,
+    /// Boards which don't match this difficulty are refused, any difficulty is
+    /// accepted if it's `None`.
+    pub difficulty: Option<Difficulty>,
+}
+
+impl Default for RecognitionOptions {
+    fn default() -> Self {
+        RecognitionOptions {
+            classifier: TileClassifier::Colors,
+            min_confidence: DEFAULT_MIN_CONFIDENCE,
+            difficulty: Some(Difficulty::Mean),
+        }
+    }
+}
+
+/// Board recognised from a screenshot.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecognitionResult {
+    pub map: MonolithMap,
+    pub geometry: GridGeometry,
+    /// Difficulty detected from the number of tile colors.
+    pub difficulty: Difficulty,
+    /// Confidence of every tile, by rows like the map.
+    pub confidence: Vec<Vec<f64>>,
+    /// Tiles recognised with a confidence below the minimum, sorted by rows.
+    pub ambiguous: Vec<Tile>,
+    /// Tiles partially covered by the cursor or an overlay, recognised from the rest.
+    pub occluded: Vec<Tile>,
+}
+
+impl RecognitionResult {
+    /// Recognised type and confidence of every tile of the grid.
+    pub fn get_tiles(&self) -> Vec<(Tile, TileRecognition)> {
+        let mut tiles = Vec::with_capacity(MAX_X * MAX_Y);
+        for x in 0..(self.geometry.columns as usize).min(MAX_X) {
+            for y in 0..(self.geometry.rows as usize).min(MAX_Y) {
+                tiles.push((
+                    (x, y),
+                    TileRecognition {
+                        tile_type: self.map.get(x, y),
+                        confidence: self.confidence[y][x],
+                    },
+                ));
+            }
+        }
+        tiles
+    }
+}
+
+/// Turns screenshots into boards.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recognizer {
+    pub geometry: GeometrySource,
+    pub colors: ColorModel,
+    pub options: RecognitionOptions,
+}
+
+impl Default for Recognizer {
+    fn default() -> Self {
+        Recognizer {
+            geometry: GeometrySource::Auto,
+            colors: ColorModel::default(),
+            options: RecognitionOptions::default(),
+        }
+    }
+}
+
+impl Recognizer {
+    pub fn new(
+        geometry: GeometrySource,
+        colors: ColorModel,
+        options: RecognitionOptions,
+    ) -> Recognizer {
+        Recognizer {
+            geometry,
+            colors,
+            options,
+        }
+    }
+
+    /// Grid of the screenshot and the color of every tile on it.
+    pub fn sample_tiles(
+        &self,
+        image: &DynamicImage,
+    ) -> Result<(GridGeometry, Vec<(Tile, TileSample)>), MyError> {
+        let geometry = self.geometry.resolve(image)?;
+        geometry.validate(image.width(), image.height())?;
+
+        let mut samples = Vec::with_capacity(MAX_X * MAX_Y);
+        for x in 0..geometry.columns.min(MAX_X as u32) {
+            for y in 0..geometry.rows.min(MAX_Y as u32) {
+                samples.push((
+                    (x as usize, y as usize),
+                    sample_tile(image, &geometry, x, y),
+                ));
+            }
+        }
+        Ok((geometry, samples))
+    }
+
+    pub fn recognize(&self, image: &DynamicImage) -> Result<RecognitionResult, MyError> {
+        let (geometry, samples) = self.sample_tiles(image)?;
+        self.recognize_samples(geometry, &samples)
+    }
+
+    /// Board from the tiles already sampled by `sample_tiles`.
+    pub fn recognize_samples(
+        &self,
+        geometry: GridGeometry,
+        samples: &[(Tile, TileSample)],
+    ) -> Result<RecognitionResult, MyError> {
+        let colors = samples.iter().map(|x| x.1.color).collect::<Vec<_>>();
+        let recognitions = self.options.classifier.classify(&colors, &self.colors);
+
+        let mut map = MonolithMap::default();
+        let mut confidence = vec![vec![1.0; MAX_X]; MAX_Y];
+        let mut ambiguous = vec![];
+        let mut occluded = vec![];
+        for ((tile, sample), recognition) in samples.iter().zip(recognitions) {
+            map.try_set(tile.0, tile.1, recognition.tile_type)?;
+            confidence[tile.1][tile.0] = recognition.confidence;
+            if recognition.confidence < self.options.min_confidence {
+                ambiguous.push(*tile);
+            }
+            if sample.occluded {
+                occluded.push(*tile);
+            }
+        }
+        ambiguous.sort_by_key(|x| (x.1, x.0));
+        occluded.sort_by_key(|x| (x.1, x.0));
+        if let Some(difficulty) = self.options.difficulty {
+            difficulty.check(&map)?;
+        }
+        Ok(RecognitionResult {
+            difficulty: Difficulty::detect(&map),
+            map,
+            geometry,
+            confidence,
+            ambiguous,
+            occluded,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{RecognitionOptions, Recognizer};
+    use crate::calibration::ColorModel;
+    use crate::cluster::TileClassifier;
+    use crate::difficulty::Difficulty;
+    use crate::geometry::{GeometrySource, GridGeometry};
+    use crate::map::{MonolithMap, MAX_X, MAX_Y};
+    use image::{DynamicImage, Rgb, RgbImage};
+
+    const PALETTE: [[u8; 3]; 5] = [
+        [28, 26, 32],
+        [189, 187, 187],
+        [236, 145, 187],
+        [211, 171, 110],
+        [99, 166, 184],
+    ];
+
+    fn get_map() -> MonolithMap {
+        // a cleared corner, the rest in diagonal stripes
+        let tile = |x, y| if x < 3 && y < 2 { 0 } else { (x + y) % 4 + 1 };
+        MonolithMap::from_fn(0..MAX_Y, tile)
+    }
+
+    fn draw_map(map: &MonolithMap, geometry: &GridGeometry, width: u32, height: u32) -> RgbImage {
+        let mut image = RgbImage::from_pixel(width, height, Rgb([10, 10, 10]));
+        for x in 0..MAX_X {
+            for y in 0..MAX_Y {
+                let left = geometry.origin_x + x as f64 * geometry.tile_size;
+                let top = geometry.origin_y + y as f64 * geometry.tile_size;
+                let size = geometry.tile_size as u32;
+                let color = Rgb(PALETTE[map.get(x, y) as usize]);
+                // one pixel gap between the tiles
+                for dx in 1..size {
+                    for dy in 1..size {
+                        image.put_pixel(left as u32 + dx, top as u32 + dy, color);
+                    }
+                }
+            }
+        }
+        image
+    }
+
+    fn get_geometry() -> GridGeometry {
+        GridGeometry {
+            origin_x: 10.0,
+            origin_y: 10.0,
+            tile_size: 20.0,
+            border_width: 2.0,
+            columns: 22,
+            rows: 11,
+        }
+    }
+
+    fn get_recognizer() -> Recognizer {
+        Recognizer::new(
+            GeometrySource::Fixed(get_geometry()),
+            ColorModel::default(),
+            RecognitionOptions::default(),
+        )
+    }
+
+    #[test]
+    fn test_recognize() {
+        let map = get_map();
+        let image = DynamicImage::ImageRgb8(draw_map(&map, &get_geometry(), 460, 240));
+        let result = get_recognizer().recognize(&image).unwrap();
+        assert_eq!(result.map, map);
+        assert_eq!(result.geometry, get_geometry());
+        assert!(result.ambiguous.is_empty());
+        assert!(result.occluded.is_empty());
+        assert_eq!(result.get_tiles().len(), MAX_X * MAX_Y);
+        assert!(result.get_tiles().iter().all(|x| x.1.confidence > 0.5));
+
+        let (geometry, samples) = get_recognizer().sample_tiles(&image).unwrap();
+        let sampled = get_recognizer().recognize_samples(geometry, &samples);
+        assert_eq!(sampled.unwrap(), result);
+    }
+
+    #[test]
+    fn test_recognize_auto_geometry() {
+        let map = get_map();
+        let geometry = GridGeometry::default().scaled(0.5);
+        let image = DynamicImage::ImageRgb8(draw_map(&map, &geometry, 960, 540));
+        let result = Recognizer::default().recognize(&image).unwrap();
+        assert_eq!(result.map, map);
+        assert_eq!(result.geometry, geometry);
+    }
+
+    #[test]
+    fn test_recognize_with_cursor_and_unknown_tile() {
+        let map = get_map();
+        let mut image = draw_map(&map, &get_geometry(), 460, 240);
+        // cursor over a part of tile (5, 5)
+        for x in 112..118 {
+            for y in 112..118 {
+                image.put_pixel(x, y, Rgb([255, 255, 255]));
+            }
+        }
+        // tile (7, 3) in a color which matches nothing
+        for x in 151..170 {
+            for y in 71..90 {
+                image.put_pixel(x, y, Rgb([120, 40, 40]));
+            }
+        }
+        let result = get_recognizer()
+            .recognize(&DynamicImage::ImageRgb8(image))
+            .unwrap();
+        assert_eq!(result.map.get(5, 5), map.get(5, 5));
+        assert_eq!(result.occluded, vec![(5, 5)]);
+        assert_eq!(result.map.get(7, 3), 0);
+        assert_eq!(result.ambiguous, vec![(7, 3)]);
+    }
+
+    #[test]
+    fn test_recognize_dark_screenshot() {
+        let map = get_map();
+        let mut image = draw_map(&map, &get_geometry(), 460, 240);
+        for pixel in image.pixels_mut() {
+            for channel in pixel.0.iter_mut() {
+                *channel = (f64::from(*channel) * 0.7) as u8;
+            }
+        }
+        let image = DynamicImage::ImageRgb8(image);
+        let mut recognizer = get_recognizer();
+        assert_ne!(recognizer.recognize(&image).unwrap().map, map);
+        recognizer.options.classifier = TileClassifier::Clusters;
+        assert_eq!(recognizer.recognize(&image).unwrap().map, map);
+    }
+
+    #[test]
+    fn test_recognize_wrong_difficulty() {
+        let mut map = get_map();
+        for x in 0..MAX_X {
+            for y in 0..MAX_Y {
+                if map.get(x, y) == 4 {
+                    map.set(x, y, 1);
+                }
+            }
+        }
+        let image = DynamicImage::ImageRgb8(draw_map(&map, &get_geometry(), 460, 240));
+        let mut recognizer = get_recognizer();
+        assert!(recognizer.recognize(&image).is_err());
+        recognizer.options.difficulty = None;
+        let result = recognizer.recognize(&image).unwrap();
+        assert_eq!(result.difficulty, Difficulty::Kind);
+        recognizer.options.difficulty = Some(Difficulty::Kind);
+        assert_eq!(recognizer.recognize(&image).unwrap(), result);
+    }
+
+    #[test]
+    fn test_grid_outside_of_image() {
+        let image = DynamicImage::new_rgb8(200, 100);
+        assert!(get_recognizer().recognize(&image).is_err());
+    }
+}
@@ -0,0 +1,261 @@
+use image::{DynamicImage, Rgb, RgbImage};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::calibration::ColorModel;
+use crate::errors::MyError;
+use crate::geometry::GridGeometry;
+use crate::map::{MonolithMap, MAX_X, MAX_Y};
+
+/// Color of the screen around the board.
+const SCREEN_COLOR: [f64; 3] = [12.0, 10.0, 16.0];
+/// Tile borders are drawn in the tile color darkened by this factor.
+const BORDER_SHADE: f64 = 0.6;
+/// Size of the cursor relative to the tile size.
+const CURSOR_SIZE: f64 = 0.4;
+
+/// How a rendered screenshot differs from a clean one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderOptions {
+    pub width: u32,
+    pub height: u32,
+    /// Largest random change of every channel of every pixel.
+    pub noise: f64,
+    /// Factor for all colors, like a gamma or color filter setting.
+    pub brightness: f64,
+    /// Encodes the image as JPEG with this quality (1-100) and decodes it again.
+    pub jpeg_quality: Option<u8>,
+    /// Tip of a mouse cursor, in pixels.
+    pub cursor: Option<(u32, u32)>,
+    /// Seed of the noise, the same seed renders the same image.
+    pub seed: u64,
+}
+
+/// Clean 1920x1080 screenshot.
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            width: 1920,
+            height: 1080,
+            noise: 0.0,
+            brightness: 1.0,
+            jpeg_quality: None,
+            cursor: None,
+            seed: 0,
+        }
+    }
+}
+
+fn to_pixel(color: [f64; 3]) -> Rgb<u8> {
+    Rgb([
+        color[0].round().clamp(0.0, 255.0) as u8,
+        color[1].round().clamp(0.0, 255.0) as u8,
+        color[2].round().clamp(0.0, 255.0) as u8,
+    ])
+}
+
+fn shade(color: [f64; 3], factor: f64) -> [f64; 3] {
+    [color[0] * factor, color[1] * factor, color[2] * factor]
+}
+
+fn fill_rect(image: &mut RgbImage, left: f64, top: f64, right: f64, bottom: f64, color: [f64; 3]) {
+    let pixel = to_pixel(color);
+    let right = (right.round() as u32).min(image.width());
+    let bottom = (bottom.round() as u32).min(image.height());
+    for x in left.round().max(0.0) as u32..right {
+        for y in top.round().max(0.0) as u32..bottom {
+            image.put_pixel(x, y, pixel);
+        }
+    }
+}
+
+/// White arrow with a black outline pointing to (x, y).
+fn draw_cursor(image: &mut RgbImage, x: u32, y: u32, size: u32) {
+    let size = size.max(4);
+    for row in 0..size {
+        let width = row / 2 + 1;
+        for column in 0..width {
+            let outline = column == 0 || column + 1 == width || row + 1 == size;
+            let color = if outline { [0, 0, 0] } else { [255, 255, 255] };
+            if x + column < image.width() && y + row < image.height() {
+                image.put_pixel(x + column, y + row, Rgb(color));
+            }
+        }
+    }
+}
+
+/// Draws the board like the game does: tiles in the colors of the model with darker
+/// borders on a dark screen, cleared cells in the background color.
+pub fn render_map(
+    map: &MonolithMap,
+    geometry: &GridGeometry,
+    colors: &ColorModel,
+    options: &RenderOptions,
+) -> Result<DynamicImage, MyError> {
+    let mut image = RgbImage::from_pixel(
+        options.width,
+        options.height,
+        to_pixel(shade(SCREEN_COLOR, options.brightness)),
+    );
+
+    let get_color = |tile_type: u8| {
+        let centroid = colors
+            .colors
+            .iter()
+            .find(|x| x.tile_type == tile_type)
+            .map_or(colors.background.centroid, |x| x.centroid);
+        shade(centroid, options.brightness)
+    };
+    for x in 0..(geometry.columns as usize).min(MAX_X) {
+        for y in 0..(geometry.rows as usize).min(MAX_Y) {
+            let color = get_color(map.get(x, y));
+            let left = geometry.origin_x + x as f64 * geometry.tile_size;
+            let top = geometry.origin_y + y as f64 * geometry.tile_size;
+            let size = geometry.tile_size;
+            let border = geometry.border_width;
+            fill_rect(
+                &mut image,
+                left,
+                top,
+                left + size,
+                top + size,
+                shade(color, BORDER_SHADE),
+            );
+            fill_rect(
+                &mut image,
+                left + border,
+                top + border,
+                left + size - border,
+                top + size - border,
+                color,
+            );
+        }
+    }
+
+    if let Some((x, y)) = options.cursor {
+        draw_cursor(&mut image, x, y, (geometry.tile_size * CURSOR_SIZE) as u32);
+    }
+
+    if options.noise > 0.0 {
+        let mut rng = StdRng::seed_from_u64(options.seed);
+        for pixel in image.pixels_mut() {
+            for channel in pixel.0.iter_mut() {
+                let value = f64::from(*channel) + rng.gen_range(-options.noise, options.noise);
+                *channel = value.round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+
+    if let Some(quality) = options.jpeg_quality {
+        let mut data = Vec::new();
+        image::jpeg::JPEGEncoder::new_with_quality(&mut data, quality.clamp(1, 100)).encode(
+            &image,
+            image.width(),
+            image.height(),
+            image::ColorType::RGB(8),
+        )?;
+        return Ok(image::load_from_memory(&data)?);
+    }
+    Ok(DynamicImage::ImageRgb8(image))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{render_map, RenderOptions};
+    use crate::calibration::ColorModel;
+    use crate::cluster::TileClassifier;
+    use crate::geometry::{GeometrySource, GridGeometry};
+    use crate::map::{MonolithMap, MAX_Y};
+    use crate::recognize::{RecognitionOptions, Recognizer};
+
+    fn get_map() -> MonolithMap {
+        MonolithMap::from_fn(0..MAX_Y, |x, y| {
+            if (x * 7 + y * 3) % 23 == 0 {
+                0
+            } else {
+                (x * x + y * 3 + x * y) % 4 + 1
+            }
+        })
+    }
+
+    fn round_trip(options: &RenderOptions, recognizer: &Recognizer) {
+        let map = get_map();
+        let geometry = GridGeometry::for_image(options.width, options.height).unwrap();
+        let image = render_map(&map, &geometry, &ColorModel::default(), options).unwrap();
+        let result = recognizer.recognize(&image).unwrap();
+        assert_eq!(result.map, map);
+    }
+
+    fn get_options() -> RenderOptions {
+        RenderOptions {
+            width: 960,
+            height: 540,
+            ..RenderOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_round_trip_clean() {
+        round_trip(&get_options(), &Recognizer::default());
+    }
+
+    #[test]
+    fn test_round_trip_noise_and_jpeg() {
+        let options = RenderOptions {
+            noise: 12.0,
+            jpeg_quality: Some(60),
+            seed: 7,
+            ..get_options()
+        };
+        round_trip(&options, &Recognizer::default());
+    }
+
+    #[test]
+    fn test_round_trip_cursor() {
+        let geometry = GridGeometry::for_image(960, 540).unwrap();
+        let (x, y, _, _) = geometry.get_tile_rect(4, 6);
+        let options = RenderOptions {
+            cursor: Some((x + 2, y + 2)),
+            ..get_options()
+        };
+        let map = get_map();
+        let image = render_map(&map, &geometry, &ColorModel::default(), &options).unwrap();
+        let result = Recognizer::default().recognize(&image).unwrap();
+        assert_eq!(result.map, map);
+        assert_eq!(result.occluded, vec![(4, 6)]);
+    }
+
+    #[test]
+    fn test_round_trip_dark_and_letterboxed() {
+        let options = RenderOptions {
+            width: 1280,
+            height: 800,
+            brightness: 0.7,
+            ..RenderOptions::default()
+        };
+        let recognizer = Recognizer::new(
+            GeometrySource::Auto,
+            ColorModel::default(),
+            RecognitionOptions {
+                classifier: TileClassifier::Clusters,
+                ..RecognitionOptions::default()
+            },
+        );
+        round_trip(&options, &recognizer);
+    }
+
+    #[test]
+    fn test_same_seed_same_image() {
+        let options = RenderOptions {
+            noise: 20.0,
+            seed: 3,
+            ..get_options()
+        };
+        let geometry = GridGeometry::for_image(960, 540).unwrap();
+        let render = || render_map(&get_map(), &geometry, &ColorModel::default(), &options);
+        assert_eq!(
+            render().unwrap().raw_pixels(),
+            render().unwrap().raw_pixels()
+        );
+    }
+}
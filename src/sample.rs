@@ -0,0 +1,150 @@
+use image::{DynamicImage, GenericImageView};
+
+use crate::calibration::Color;
+use crate::geometry::GridGeometry;
+
+/// The sampled area of a tile is split into this many patches along each side.
+const PATCHES: u32 = 4;
+/// Patches which differ from the median of the tile by more than this on any channel
+/// are covered by something else (cursor, tooltip, overlay) and left out.
+const OUTLIER_DIFFERENCE: f64 = 32.0;
+
+/// Color of one tile on a screenshot.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileSample {
+    pub color: Color,
+    /// Part of the tile didn't match the rest and was left out of the color.
+    pub occluded: bool,
+}
+
+/// Root mean square of every channel of the pixels in the rectangle.
+fn get_patch_color(image: &DynamicImage, rect: (u32, u32, u32, u32)) -> [f64; 3] {
+    let (x, y, width, height) = rect;
+    let mut sums = [0.0; 3];
+    let mut count: f64 = 0.0;
+    for pixel in image.view(x, y, width, height).pixels() {
+        count += 1.0;
+        for (sum, value) in sums.iter_mut().zip(pixel.2 .0.iter()) {
+            *sum += f64::from(*value).powi(2);
+        }
+    }
+    let mut color = [0.0; 3];
+    for (channel, sum) in color.iter_mut().zip(&sums) {
+        *channel = (sum / count.max(1.0)).sqrt();
+    }
+    color
+}
+
+fn get_median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let middle = values.len() / 2;
+    if values.len() % 2 == 0 {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    }
+}
+
+/// Color of the tile from a grid of patches: patches far from the median color are
+/// rejected and the rest is averaged, so a cursor or an overlay over a part of the tile
+/// doesn't change its color.
+pub fn sample_tile(
+    image: &DynamicImage,
+    geometry: &GridGeometry,
+    tile_x: u32,
+    tile_y: u32,
+) -> TileSample {
+    let (x, y, width, height) = geometry.get_tile_rect(tile_x, tile_y);
+    let patches_x = PATCHES.min(width).max(1);
+    let patches_y = PATCHES.min(height).max(1);
+    let mut patches = Vec::with_capacity((patches_x * patches_y) as usize);
+    for patch_y in 0..patches_y {
+        for patch_x in 0..patches_x {
+            let left = x + width * patch_x / patches_x;
+            let top = y + height * patch_y / patches_y;
+            let right = x + width * (patch_x + 1) / patches_x;
+            let bottom = y + height * (patch_y + 1) / patches_y;
+            patches.push(get_patch_color(
+                image,
+                (left, top, right - left, bottom - top),
+            ));
+        }
+    }
+
+    let mut median = [0.0; 3];
+    for (channel, value) in median.iter_mut().enumerate() {
+        *value = get_median(&mut patches.iter().map(|x| x[channel]).collect::<Vec<_>>());
+    }
+    let inliers = patches
+        .iter()
+        .filter(|patch| {
+            patch
+                .iter()
+                .zip(&median)
+                .all(|(value, median)| (value - median).abs() <= OUTLIER_DIFFERENCE)
+        })
+        .collect::<Vec<_>>();
+    // two halves too different to tell which one is the tile
+    let inliers = if inliers.is_empty() {
+        patches.iter().collect()
+    } else {
+        inliers
+    };
+
+    let mut color = [0.0; 3];
+    for (channel, value) in color.iter_mut().enumerate() {
+        *value =
+            (inliers.iter().map(|x| x[channel].powi(2)).sum::<f64>() / inliers.len() as f64).sqrt();
+    }
+    TileSample {
+        color: (color[0] as u8, color[1] as u8, color[2] as u8),
+        occluded: inliers.len() < patches.len(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{sample_tile, TileSample};
+    use crate::geometry::GridGeometry;
+    use image::{DynamicImage, Rgb, RgbImage};
+
+    fn get_geometry() -> GridGeometry {
+        GridGeometry {
+            origin_x: 0.0,
+            origin_y: 0.0,
+            tile_size: 40.0,
+            border_width: 2.0,
+            columns: 2,
+            rows: 1,
+        }
+    }
+
+    #[test]
+    fn test_sample_tile() {
+        let image = RgbImage::from_pixel(80, 40, Rgb([99, 166, 184]));
+        let sample = sample_tile(&DynamicImage::ImageRgb8(image), &get_geometry(), 1, 0);
+        assert_eq!(
+            sample,
+            TileSample {
+                color: (99, 166, 184),
+                occluded: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_sample_occluded_tile() {
+        let mut image = RgbImage::from_pixel(80, 40, Rgb([236, 145, 187]));
+        // white cursor over the top left corner of the second tile
+        for x in 42..58 {
+            for y in 2..16 {
+                image.put_pixel(x, y, Rgb([255, 255, 255]));
+            }
+        }
+        let image = DynamicImage::ImageRgb8(image);
+        let sample = sample_tile(&image, &get_geometry(), 1, 0);
+        assert_eq!(sample.color, (236, 145, 187));
+        assert!(sample.occluded);
+        assert!(!sample_tile(&image, &get_geometry(), 0, 0).occluded);
+    }
+}
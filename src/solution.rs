@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::errors::{MyError, WithPath};
+use crate::map::{MonolithMap, SolverOptions, SolvingMethods, Tile, MAX_X, MAX_Y};
+
+/// Schema version of the solution files written by this program.
+pub const SOLUTION_VERSION: u32 = 1;
+
+/// Click of a solution with its expected result.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SolutionStep {
+    pub tile: Tile,
+    /// Size of the group which the click removes.
+    pub group_size: usize,
+    /// Board after the click.
+    pub board: MonolithMap,
+}
+
+/// Solved board as written to the `.solution.json` file next to the `.ahk` script.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Solution {
+    pub version: u32,
+    /// Board before the first click.
+    pub map: MonolithMap,
+    pub method: SolvingMethods,
+    pub options: SolverOptions,
+    pub steps: Vec<SolutionStep>,
+    /// Tiles left after the last click.
+    pub remaining_tiles: u32,
+    /// In-game score with the `score_rules` of the options.
+    pub score: u32,
+    /// Time the solver took, in seconds.
+    pub solve_time: f64,
+}
+
+impl Solution {
+    /// Plays the clicks on the board to record every step.
+    pub fn new(
+        map: MonolithMap,
+        method: SolvingMethods,
+        options: SolverOptions,
+        clicks: &[Tile],
+        solve_time: Duration,
+    ) -> Solution {
+        let mut board = map.clone();
+        let mut steps = Vec::with_capacity(clicks.len());
+        for tile in clicks {
+            let group_size = board.get_group(tile.0, tile.1).len();
+            board.click(tile.0, tile.1);
+            steps.push(SolutionStep {
+                tile: *tile,
+                group_size,
+                board: board.clone(),
+            });
+        }
+        Solution {
+            version: SOLUTION_VERSION,
+            remaining_tiles: board.get_all_tiles_count(),
+            score: options.score_rules.get_score(&map, clicks),
+            map,
+            method,
+            options,
+            steps,
+            solve_time: solve_time.as_secs_f64(),
+        }
+    }
+
+    pub fn clicks(&self) -> Vec<Tile> {
+        self.steps.iter().map(|x| x.tile).collect()
+    }
+
+    /// Replays the clicks and checks that every one is on the board, removes a group
+    /// and leaves the recorded board, and that the recorded number of tiles is left.
+    pub fn verify(&self) -> Result<(), MyError> {
+        let mut board = self.map.clone();
+        for (index, step) in self.steps.iter().enumerate() {
+            if step.tile.0 >= MAX_X || step.tile.1 >= MAX_Y {
+                return Err(MyError::InvalidInput(format!(
+                    "click {} at {:?} is outside of the board",
+                    index + 1,
+                    step.tile
+                )));
+            }
+            let group_size = board.get_group(step.tile.0, step.tile.1).len();
+            if group_size < 2 || group_size != step.group_size {
+                return Err(MyError::InvalidInput(format!(
+                    "click {} at {:?} removes {} tiles instead of {}",
+                    index + 1,
+                    step.tile,
+                    group_size,
+                    step.group_size
+                )));
+            }
+            board.click(step.tile.0, step.tile.1);
+            if board != step.board {
+                return Err(MyError::InvalidInput(format!(
+                    "the board after click {} at {:?} is different",
+                    index + 1,
+                    step.tile
+                )));
+            }
+        }
+        if board.get_all_tiles_count() != self.remaining_tiles {
+            return Err(MyError::InvalidInput(format!(
+                "{} tiles are left instead of {}",
+                board.get_all_tiles_count(),
+                self.remaining_tiles
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Solution, MyError> {
+        let mut file = std::fs::File::open(path).with_path(path)?;
+        let mut buffer = String::new();
+        file.read_to_string(&mut buffer).with_path(path)?;
+        serde_json::from_str::<Solution>(&buffer).with_path(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), MyError> {
+        let file = std::fs::File::create(path).with_path(path)?;
+        serde_json::to_writer(file, self).with_path(path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Solution;
+    use crate::map::{MonolithMap, Objectives, SolverOptions, SolvingMethods, MAX_X, MAX_Y};
+    use std::time::Duration;
+
+    fn get_solution() -> Solution {
+        let map = MonolithMap::from_fn(MAX_Y - 3..MAX_Y, |x, y| (x * 5 + y * 3 + x * y) % 4 + 1);
+        let options = SolverOptions {
+            objectives: "tiles,clicks".parse::<Objectives>().unwrap(),
+            ..SolverOptions::default()
+        };
+        let clicks = SolvingMethods::Method10
+            .solve_with(map.clone(), &options)
+            .unwrap();
+        Solution::new(
+            map,
+            SolvingMethods::Method10,
+            options,
+            &clicks,
+            Duration::from_millis(1500),
+        )
+    }
+
+    #[test]
+    fn test_solution() {
+        let solution = get_solution();
+        assert!(!solution.steps.is_empty());
+        assert!(solution.steps.iter().all(|x| x.group_size >= 2));
+        let last = &solution.steps[solution.steps.len() - 1].board;
+        assert_eq!(last.get_all_tiles_count(), solution.remaining_tiles);
+        assert_eq!(solution.solve_time, 1.5);
+        assert!(solution.verify().is_ok());
+
+        let json = serde_json::to_string(&solution).unwrap();
+        let loaded = serde_json::from_str::<Solution>(&json).unwrap();
+        assert_eq!(loaded, solution);
+        assert_eq!(loaded.clicks(), solution.clicks());
+    }
+
+    #[test]
+    fn test_verify() {
+        let mut solution = get_solution();
+        solution.remaining_tiles += 1;
+        assert!(solution.verify().is_err());
+
+        let mut solution = get_solution();
+        solution.steps.swap(0, 1);
+        assert!(solution.verify().is_err());
+
+        let mut solution = get_solution();
+        solution.steps[0].board = solution.map.clone();
+        assert!(solution.verify().is_err());
+
+        let mut solution = get_solution();
+        solution.steps[0].tile = (MAX_X, 0);
+        assert!(solution.verify().is_err());
+        solution.steps[0].tile = (0, MAX_Y + 5);
+        assert!(solution.verify().is_err());
+    }
+}
@@ -0,0 +1,280 @@
+use rand::rngs::ThreadRng;
+use rand::{thread_rng, Rng};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+
+use crate::errors::MyError;
+use crate::map::{HeuristicWeights, MonolithMap, SolverOptions, SolvingMethods, Tile};
+
+const WORKER_COUNT: usize = 8;
+const POPULATION_SIZE: usize = 10;
+const ELITE_SIZE: usize = 3;
+const MIN_SIGMA: f64 = 0.01;
+
+#[derive(Debug, Clone)]
+pub enum TuningStrategy {
+    /// Uniformly sampled weight vectors.
+    Random,
+    /// Evolution strategy which moves the sampling distribution
+    /// towards the best candidates of every generation.
+    Evolution,
+}
+
+impl FromStr for TuningStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(Self::Random),
+            "evolution" => Ok(Self::Evolution),
+            _ => Err(format!("Unknown tuning strategy '{}'", s)),
+        }
+    }
+}
+
+/// Loads all `*.map` files from the directory, sorted by name.
+pub fn load_maps(directory: &Path) -> Result<Vec<MonolithMap>, MyError> {
+    let mut paths = vec![];
+    for entry in directory.read_dir()? {
+        let path = entry?.path();
+        if let Some(extension) = path.extension() {
+            if extension.to_string_lossy().to_lowercase() == "map" {
+                paths.push(path);
+            }
+        }
+    }
+    paths.sort();
+
+    let mut maps = Vec::with_capacity(paths.len());
+    for path in paths {
+        maps.push(MonolithMap::load(&path)?);
+    }
+    Ok(maps)
+}
+
+fn get_remaining_tiles(map: &MonolithMap, steps: &[Tile]) -> u32 {
+    let mut map = map.clone();
+    for step in steps {
+        map.click(step.0, step.1);
+    }
+    map.get_all_tiles_count()
+}
+
+/// Solves every map with the given options and returns the mean count of tiles left.
+pub fn mean_remaining_tiles(
+    maps: &[MonolithMap],
+    method: &SolvingMethods,
+    options: &SolverOptions,
+) -> Result<f64, MyError> {
+    if maps.is_empty() {
+        return Ok(0.0);
+    }
+
+    let shared_maps = Arc::new(maps.to_vec());
+    let workers: Vec<_> = (0..WORKER_COUNT)
+        .map(|index| {
+            let maps = shared_maps.clone();
+            let method = method.clone();
+            let options = options.clone();
+            thread::spawn(move || {
+                maps.iter()
+                    .skip(index)
+                    .step_by(WORKER_COUNT)
+                    .map(|map| {
+                        let steps = method.clone().solve_with(map.clone(), &options)?;
+                        Ok(get_remaining_tiles(map, &steps))
+                    })
+                    .sum::<Result<u32, MyError>>()
+            })
+        })
+        .collect();
+
+    let mut total = 0;
+    for worker in workers {
+        total += worker.join().map_err(|_| MyError::WorkerPanicked)??;
+    }
+    Ok(f64::from(total) / maps.len() as f64)
+}
+
+fn random_weights(rng: &mut ThreadRng) -> Vec<f64> {
+    (0..HeuristicWeights::LEN)
+        .map(|_| rng.gen_range(-1.0, 1.0))
+        .collect()
+}
+
+/// Standard normal sample (Box-Muller transform).
+fn gaussian(rng: &mut ThreadRng) -> f64 {
+    let u1: f64 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+/// Searches for the weight vector which minimises the mean count of remaining tiles.
+/// `iterations` is the number of evaluated candidates; the weights from `options` are
+/// always evaluated first, so the result is never worse than them.
+pub fn tune(
+    maps: &[MonolithMap],
+    method: &SolvingMethods,
+    options: &SolverOptions,
+    strategy: &TuningStrategy,
+    iterations: usize,
+) -> Result<(HeuristicWeights, f64), MyError> {
+    let mut rng = thread_rng();
+    let mut best_weights = options.weights.clone();
+    let mut best_score = mean_remaining_tiles(maps, method, options)?;
+    println!("Initial weights leave {:.2} tiles on average.", best_score);
+
+    let mut try_candidate =
+        |values: &[f64], best_weights: &mut HeuristicWeights| -> Result<f64, MyError> {
+            let candidate_options = SolverOptions {
+                weights: HeuristicWeights::from_slice(values),
+                ..options.clone()
+            };
+            let score = mean_remaining_tiles(maps, method, &candidate_options)?;
+            if score < best_score {
+                best_score = score;
+                *best_weights = candidate_options.weights;
+                println!("Found weights leaving {:.2} tiles on average.", score);
+            }
+            Ok(score)
+        };
+
+    match strategy {
+        TuningStrategy::Random => {
+            for _ in 0..iterations {
+                let candidate = random_weights(&mut rng);
+                try_candidate(&candidate, &mut best_weights)?;
+            }
+        }
+        TuningStrategy::Evolution => {
+            let mut mean = best_weights.to_vec();
+            let mut sigma = [0.5; HeuristicWeights::LEN];
+            let mut evaluated = 0;
+            while evaluated < iterations {
+                let population = POPULATION_SIZE.min(iterations - evaluated);
+                let mut generation = (0..population)
+                    .map(|_| {
+                        let candidate = mean
+                            .iter()
+                            .zip(sigma.iter())
+                            .map(|(m, s)| m + s * gaussian(&mut rng))
+                            .collect::<Vec<f64>>();
+                        Ok((try_candidate(&candidate, &mut best_weights)?, candidate))
+                    })
+                    .collect::<Result<Vec<_>, MyError>>()?;
+                evaluated += population;
+
+                generation
+                    .sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+                let elite = &generation[..ELITE_SIZE.min(generation.len())];
+                let elite_len = elite.len() as f64;
+                for i in 0..HeuristicWeights::LEN {
+                    let new_mean = elite.iter().map(|x| x.1[i]).sum::<f64>() / elite_len;
+                    let variance = elite
+                        .iter()
+                        .map(|x| (x.1[i] - mean[i]).powi(2))
+                        .sum::<f64>()
+                        / elite_len;
+                    mean[i] = new_mean;
+                    sigma[i] = variance.sqrt().max(MIN_SIGMA);
+                }
+            }
+        }
+    }
+
+    Ok((best_weights, best_score))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{load_maps, mean_remaining_tiles, tune, TuningStrategy};
+    use crate::map::{MonolithMap, SolverOptions, SolvingMethods};
+
+    fn get_maps() -> Vec<MonolithMap> {
+        vec![
+            "
+                ......................
+                ......................
+                ......................
+                ......................
+                ......................
+                ......................
+                ......................
+                ......332.............
+                ........11.......242..
+                ..3.....32............
+                ..4...................
+            "
+            .parse::<MonolithMap>()
+            .unwrap(),
+            "
+                ......................
+                ......................
+                ......................
+                .........44...........
+                .........213..........
+                .........213..........
+                ..........2...........
+                ......................
+                ......................
+                ......................
+                ......................
+            "
+            .parse::<MonolithMap>()
+            .unwrap(),
+        ]
+    }
+
+    #[test]
+    fn test_mean_remaining_tiles() {
+        let maps = get_maps();
+        let options = SolverOptions::default();
+        let mean = mean_remaining_tiles(&maps, &SolvingMethods::Method10, &options).unwrap();
+        assert!((0.0..10.0).contains(&mean));
+        assert_eq!(
+            mean_remaining_tiles(&[], &SolvingMethods::Method10, &options).unwrap(),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_tune_is_not_worse_than_default() {
+        let maps = get_maps();
+        let method = SolvingMethods::Method10;
+        let options = SolverOptions::default();
+        let default_score = mean_remaining_tiles(&maps, &method, &options).unwrap();
+        for strategy in &[TuningStrategy::Random, TuningStrategy::Evolution] {
+            let (weights, score) = tune(&maps, &method, &options, strategy, 12).unwrap();
+            assert!(score <= default_score);
+            let tuned_options = SolverOptions {
+                weights,
+                ..SolverOptions::default()
+            };
+            assert_eq!(
+                mean_remaining_tiles(&maps, &method, &tuned_options).unwrap(),
+                score
+            );
+        }
+    }
+
+    #[test]
+    fn test_load_maps() {
+        let directory = std::env::temp_dir().join(format!(
+            "monolith_solver_test_load_maps_{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&directory).unwrap();
+        let maps = get_maps();
+        for (index, map) in maps.iter().enumerate() {
+            let file = std::fs::File::create(directory.join(format!("{}.map", index))).unwrap();
+            serde_json::to_writer(file, map).unwrap();
+        }
+        std::fs::write(directory.join("notes.txt"), "not a map").unwrap();
+
+        let loaded = load_maps(&directory).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(loaded, maps);
+    }
+}
@@ -0,0 +1,248 @@
+use image::{DynamicImage, RgbImage};
+use std::io::Read;
+use std::path::PathBuf;
+
+use crate::errors::{MyError, WithPath};
+use crate::map::{MonolithMap, MAX_X, MAX_Y};
+use crate::recognize::{RecognitionResult, Recognizer};
+
+/// A board counts as shown once it is recognised the same in this many frames in a row,
+/// so frames in the middle of a fade or an animation aren't taken.
+pub const DEFAULT_STABLE_FRAMES: usize = 2;
+
+/// Frames piped from ffmpeg with `-f rawvideo -pix_fmt rgb24`: plain RGB pixels without
+/// any header, so the frame size has to be known.
+pub struct RawFrames<R: Read> {
+    reader: R,
+    width: u32,
+    height: u32,
+}
+
+impl<R: Read> RawFrames<R> {
+    pub fn new(reader: R, width: u32, height: u32) -> RawFrames<R> {
+        RawFrames {
+            reader,
+            width,
+            height,
+        }
+    }
+}
+
+impl<R: Read> Iterator for RawFrames<R> {
+    type Item = Result<DynamicImage, MyError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let size = (self.width as usize)
+            .checked_mul(self.height as usize)
+            .and_then(|pixels| pixels.checked_mul(3));
+        let size = match size {
+            Some(size) => size,
+            None => {
+                return Some(Err(MyError::InvalidInput(format!(
+                    "the frame size {}x{} is too large",
+                    self.width, self.height
+                ))))
+            }
+        };
+        let mut data = vec![0; size];
+        let mut filled = 0;
+        while filled < data.len() {
+            match self.reader.read(&mut data[filled..]) {
+                Ok(0) => break,
+                Ok(count) => filled += count,
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => (),
+                Err(error) => return Some(Err(error.into())),
+            }
+        }
+        if filled == 0 {
+            return None;
+        }
+        if filled < data.len() {
+            return Some(Err(MyError::InvalidInput(format!(
+                "the last frame has {} of {} bytes, is the frame size {}x{}?",
+                filled,
+                data.len(),
+                self.width,
+                self.height
+            ))));
+        }
+        match RgbImage::from_raw(self.width, self.height, data) {
+            Some(image) => Some(Ok(DynamicImage::ImageRgb8(image))),
+            None => Some(Err(MyError::InvalidInput(format!(
+                "the frame doesn't fit the frame size {}x{}",
+                self.width, self.height
+            )))),
+        }
+    }
+}
+
+/// Frames saved as image files, in the given order.
+pub fn read_frame_files(
+    paths: Vec<PathBuf>,
+) -> impl Iterator<Item = Result<DynamicImage, MyError>> {
+    paths
+        .into_iter()
+        .map(|path| image::open(&path).with_path(&path))
+}
+
+/// Frame of a recording with a recognised board.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoardFrame {
+    /// Index of the frame, counted from 0.
+    pub index: usize,
+    pub recognition: RecognitionResult,
+}
+
+fn is_full_board(map: &MonolithMap) -> bool {
+    map.get_all_tiles_count() as usize == MAX_X * MAX_Y
+}
+
+/// Watches the recognised frames of a recording for a new board: every cell has a tile,
+/// no tile is ambiguous and the board stays the same for a few frames. Frames without a
+/// board (menus, a board being played or cleared) aren't recognised or have empty cells.
+#[derive(Debug, Clone)]
+pub struct BoardDetector {
+    stable_frames: usize,
+    candidate: Option<BoardFrame>,
+    count: usize,
+}
+
+impl BoardDetector {
+    pub fn new(stable_frames: usize) -> BoardDetector {
+        BoardDetector {
+            stable_frames: stable_frames.max(1),
+            candidate: None,
+            count: 0,
+        }
+    }
+
+    /// Adds the recognition of the next frame, returns the first frame of the board
+    /// once it has been stable long enough.
+    pub fn push(
+        &mut self,
+        index: usize,
+        recognition: Result<RecognitionResult, MyError>,
+    ) -> Option<BoardFrame> {
+        let recognition = match recognition {
+            Ok(recognition)
+                if recognition.ambiguous.is_empty() && is_full_board(&recognition.map) =>
+            {
+                recognition
+            }
+            _ => {
+                self.candidate = None;
+                self.count = 0;
+                return None;
+            }
+        };
+
+        match &self.candidate {
+            Some(candidate) if candidate.recognition.map == recognition.map => self.count += 1,
+            _ => {
+                self.candidate = Some(BoardFrame { index, recognition });
+                self.count = 1;
+            }
+        }
+        if self.count == self.stable_frames {
+            self.candidate.clone()
+        } else {
+            None
+        }
+    }
+}
+
+/// First frame where a full board appears, `None` if there is none in the recording.
+/// Frames which can't be read stop the search with an error.
+pub fn find_new_board<I>(
+    frames: I,
+    recognizer: &Recognizer,
+    stable_frames: usize,
+) -> Result<Option<BoardFrame>, MyError>
+where
+    I: Iterator<Item = Result<DynamicImage, MyError>>,
+{
+    let mut detector = BoardDetector::new(stable_frames);
+    for (index, frame) in frames.enumerate() {
+        if let Some(board) = detector.push(index, recognizer.recognize(&frame?)) {
+            return Ok(Some(board));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{find_new_board, RawFrames};
+    use crate::calibration::ColorModel;
+    use crate::errors::MyError;
+    use crate::geometry::GridGeometry;
+    use crate::map::{MonolithMap, MAX_X, MAX_Y};
+    use crate::recognize::Recognizer;
+    use crate::render::{render_map, RenderOptions};
+    use image::DynamicImage;
+
+    fn get_map(seed: usize) -> MonolithMap {
+        MonolithMap::from_fn(0..MAX_Y, |x, y| (x * seed + y * 3 + x * y) % 4 + 1)
+    }
+
+    fn render(map: &MonolithMap) -> Result<DynamicImage, MyError> {
+        let options = RenderOptions {
+            width: 960,
+            height: 540,
+            ..RenderOptions::default()
+        };
+        let geometry = GridGeometry::for_image(options.width, options.height)?;
+        render_map(map, &geometry, &ColorModel::default(), &options)
+    }
+
+    #[test]
+    fn test_find_new_board() {
+        let board = get_map(5);
+        // the tiles of the new board appear column by column
+        let mut appearing = board.clone();
+        for x in 10..MAX_X {
+            for y in 0..MAX_Y {
+                appearing.set(x, y, 0);
+            }
+        }
+        // a single frame of another full board, in the middle of a transition
+        let frames = vec![
+            Ok(DynamicImage::new_rgb8(960, 540)),
+            render(&MonolithMap::default()),
+            render(&appearing),
+            render(&get_map(2)),
+            render(&board),
+            render(&board),
+            render(&board),
+        ];
+        let found = find_new_board(frames.into_iter(), &Recognizer::default(), 2)
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.index, 4);
+        assert_eq!(found.recognition.map, board);
+
+        let frames = vec![render(&appearing), render(&get_map(2))];
+        let found = find_new_board(frames.into_iter(), &Recognizer::default(), 2).unwrap();
+        assert_eq!(found, None);
+    }
+
+    #[test]
+    fn test_raw_frames() {
+        let mut data = vec![];
+        for frame in 0..3u8 {
+            data.extend(vec![frame * 10; 4 * 2 * 3]);
+        }
+        let frames = RawFrames::new(&data[..], 4, 2)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[2].to_rgb().get_pixel(3, 1).0, [20, 20, 20]);
+
+        let mut frames = RawFrames::new(&data[..30], 4, 2);
+        assert!(frames.next().unwrap().is_ok());
+        assert!(frames.next().unwrap().is_err());
+
+        let mut frames = RawFrames::new(&data[..], u32::MAX, u32::MAX);
+        assert!(frames.next().unwrap().is_err());
+    }
+}